@@ -8,7 +8,7 @@ use crate::{
     write_indented_by, write_items, write_items_indented, writeln_indented,
 };
 
-use self::types::{ArrayType, FunctionType, TupleType, TypeBounds};
+use self::types::{ArrayType, FunctionType, RecordType, TupleType, TypeBounds};
 
 use super::{asm::*, *};
 
@@ -971,6 +971,8 @@ impl<E: Display> Display for Type<E> {
             Type::Array(array) => write!(f, "{array}"),
             Type::Tuple(tuple) => write!(f, "{tuple}"),
             Type::Function(fun) => write!(f, "{fun}"),
+            Type::Record(record) => write!(f, "{record}"),
+            Type::Optional(inner) => write!(f, "{}?", format_type_with_parentheses(inner)),
             Type::TypeVar(name) => write!(f, "{name}"),
             Type::NamedType(name, Some(args)) => {
                 write!(f, "{name}{}", format_type_args(args))
@@ -1009,6 +1011,19 @@ impl<E: Display> Display for FunctionType<E> {
     }
 }
 
+impl<E: Display> Display for RecordType<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "{{{}}}",
+            self.fields
+                .iter()
+                .map(|(name, ty)| format!("{name}: {}", format_type_with_parentheses(ty)))
+                .format(", ")
+        )
+    }
+}
+
 fn format_type_with_parentheses<E: Display>(name: &Type<E>) -> String {
     if name.needs_parentheses() {
         format!("({name})")
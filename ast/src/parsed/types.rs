@@ -5,10 +5,20 @@ use std::{
 };
 
 use itertools::Itertools;
+use powdr_number::FieldElement;
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use super::{asm::SymbolPath, display::type_vars_to_string, visitor::Children, Expression, Number};
+use super::{
+    asm::{Part, SymbolPath},
+    display::type_vars_to_string,
+    visitor::Children,
+    Expression, Number,
+};
+
+mod sexpr;
+
+pub use sexpr::SexprError;
 
 #[derive(
     Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize, JsonSchema,
@@ -34,6 +44,10 @@ pub enum Type<E = u64> {
     Array(ArrayType<E>),
     Tuple(TupleType<E>),
     Function(FunctionType<E>),
+    /// A tuple with named fields.
+    Record(RecordType<E>),
+    /// A value of the inner type that might not be present, written `T?`.
+    Optional(Box<Type<E>>),
     TypeVar(String),
     /// A named type like an enum, including generic arguments.
     /// Directly after parsing, type variables are also
@@ -57,16 +71,89 @@ impl<E> Type<E> {
             Type::Array(_)
             | Type::Tuple(_)
             | Type::Function(_)
+            | Type::Record(_)
+            | Type::Optional(_)
             | Type::TypeVar(_)
             | Type::NamedType(_, _) => false,
         }
     }
+
+    /// Returns the keyword of an elementary type as written in the source,
+    /// e.g. `fe` for `Type::Fe` and `!` for `Type::Bottom`, and None for all
+    /// other types.
+    pub fn elementary_name(&self) -> Option<&'static str> {
+        match self {
+            Type::Bottom => Some("!"),
+            Type::Bool => Some("bool"),
+            Type::Int => Some("int"),
+            Type::Fe => Some("fe"),
+            Type::String => Some("string"),
+            Type::Col => Some("col"),
+            Type::Inter => Some("inter"),
+            Type::Expr => Some("expr"),
+            Type::Array(_)
+            | Type::Tuple(_)
+            | Type::Function(_)
+            | Type::Record(_)
+            | Type::Optional(_)
+            | Type::TypeVar(_)
+            | Type::NamedType(_, _) => None,
+        }
+    }
+
+    /// Returns the inner type if this is an optional type.
+    pub fn inner_optional(&self) -> Option<&Type<E>> {
+        match self {
+            Type::Optional(inner) => Some(inner),
+            _ => None,
+        }
+    }
+
+    /// Returns true if this is the type of constraints, i.e. the (resolved)
+    /// named type `std::prelude::Constr`.
+    pub fn is_constraint(&self) -> bool {
+        const CONSTR: [&str; 3] = ["std", "prelude", "Constr"];
+        match self {
+            Type::NamedType(name, None) => {
+                name.parts().len() == CONSTR.len()
+                    && name
+                        .parts()
+                        .zip(CONSTR)
+                        .all(|(part, expected)| matches!(part, Part::Named(n) if n == expected))
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if this type is or contains the type of constraints, e.g.
+    /// as an array element or the return type of a function.
+    pub fn produces_constraints(&self) -> bool {
+        self.is_constraint() || self.children().any(|t| t.produces_constraints())
+    }
+
+    /// Returns the number of nodes of this type, where every elementary type,
+    /// type variable, named type and composite type counts as one node, e.g.
+    /// `(int, T[])` has four nodes.
+    pub fn count_nodes(&self) -> usize {
+        1 + self.children().map(|t| t.count_nodes()).sum::<usize>()
+    }
+
+    /// Returns true if `fe` occurs anywhere in this type.
+    fn contains_fe(&self) -> bool {
+        matches!(self, Type::Fe) || self.children().any(|t| t.contains_fe())
+    }
+
     /// Returns true if the type name needs parentheses during formatting
     /// when used inside a complex expression.
     pub fn needs_parentheses(&self) -> bool {
         match self {
             _ if self.is_elementary() => false,
-            Type::Array(_) | Type::Tuple(_) | Type::TypeVar(_) | Type::NamedType(_, _) => false,
+            Type::Array(_)
+            | Type::Tuple(_)
+            | Type::Record(_)
+            | Type::Optional(_)
+            | Type::TypeVar(_)
+            | Type::NamedType(_, _) => false,
             Type::Function(_) => true,
             _ => unreachable!(),
         }
@@ -118,10 +205,22 @@ impl<E> Type<E> {
         }
     }
 
+    /// Returns true if the type does not contain type variables (i.e. it is a "ground" type).
+    /// Array lengths do not need to be known, see `is_fully_monomorphic`.
     pub fn is_concrete_type(&self) -> bool {
         self.contained_type_vars_with_repetitions().next().is_none()
     }
 
+    /// Returns true if the type does not contain type variables and the lengths
+    /// of all arrays are known. For example, `int[]` is concrete but not fully
+    /// monomorphic, while `int[2]` is both.
+    pub fn is_fully_monomorphic(&self) -> bool {
+        match self {
+            Type::TypeVar(_) | Type::Array(ArrayType { length: None, .. }) => false,
+            _ => self.children().all(|t| t.is_fully_monomorphic()),
+        }
+    }
+
     pub fn contains_type_var(&self, name: &str) -> bool {
         self.contained_type_vars_with_repetitions()
             .any(|n| n == name)
@@ -135,6 +234,35 @@ impl<E> Type<E> {
     pub fn empty_tuple() -> Type<E> {
         Type::Tuple(TupleType { items: vec![] })
     }
+
+    /// Returns the unit type `()`, i.e. the empty tuple.
+    pub fn unit() -> Type<E> {
+        Self::empty_tuple()
+    }
+
+    /// Returns true if this is the unit type `()`, i.e. the empty tuple.
+    /// Note that `normalize` keeps empty tuples, it only unwraps single-element tuples.
+    pub fn is_unit(&self) -> bool {
+        matches!(self, Type::Tuple(TupleType { items }) if items.is_empty())
+    }
+
+    /// Returns the type of a numeric literal that appears in a context where
+    /// a value of type `expected` is required, or None if a numeric literal
+    /// cannot have such a type.
+    ///
+    /// Literals take the type of an `int`, `fe` or `expr` context, i.e. they are
+    /// integers, field elements or constant algebraic expressions. Type variables
+    /// are returned unchanged, they are resolved later during type inference.
+    /// Columns and intermediate columns cannot be literals.
+    pub fn coerce_literal(expected: &Type<E>) -> Option<Type<E>> {
+        match expected {
+            Type::Int => Some(Type::Int),
+            Type::Fe => Some(Type::Fe),
+            Type::Expr => Some(Type::Expr),
+            Type::TypeVar(n) => Some(Type::TypeVar(n.clone())),
+            _ => None,
+        }
+    }
 }
 
 impl<E: ExpressionInArrayLength> Type<E> {
@@ -207,227 +335,1577 @@ impl<E: Clone> Type<E> {
                 .for_each(|t| t.substitute_type_vars(substitutions)),
         }
     }
-}
 
-impl<E> Type<E> {
-    fn contained_type_vars_with_repetitions(&self) -> Box<dyn Iterator<Item = &String> + '_> {
-        match self {
-            Type::TypeVar(n) => Box::new(std::iter::once(n)),
-            _ => Box::new(
-                self.children()
-                    .flat_map(|t| t.contained_type_vars_with_repetitions()),
-            ),
-        }
+    /// Renames the type variables to `T1`, `T2`, ... in the order of their first occurrence.
+    pub fn rename_type_vars(mut self) -> Type<E> {
+        let substitutions = self
+            .contained_type_vars()
+            .enumerate()
+            .map(|(i, n)| (n.clone(), Type::TypeVar(format!("T{}", i + 1))))
+            .collect();
+        self.substitute_type_vars(&substitutions);
+        self
     }
-}
 
-/// Returns iterators over all direct sub-types for this type.
-impl<E> Children<Type<E>> for Type<E> {
-    fn children(&self) -> Box<dyn Iterator<Item = &Type<E>> + '_> {
-        match self {
-            Type::Array(ar) => Box::new(std::iter::once(&*ar.base)),
-            Type::Tuple(tu) => Box::new(tu.items.iter()),
-            Type::Function(fun) => Box::new(fun.params.iter().chain(std::iter::once(&*fun.value))),
-            Type::TypeVar(_) | Type::NamedType(_, None) => Box::new(std::iter::empty()),
-            Type::NamedType(_, Some(args)) => Box::new(args.iter()),
-            _ => {
-                assert!(self.is_elementary());
-                Box::new(std::iter::empty())
+    /// Renames the type variables according to `mapping`, type variables
+    /// not contained in `mapping` are kept.
+    pub fn rename_type_vars_with(&mut self, mapping: &HashMap<String, String>) {
+        self.walk_mut(&mut |t| {
+            if let Type::TypeVar(n) = t {
+                if let Some(new_name) = mapping.get(n) {
+                    *n = new_name.clone();
+                }
             }
-        }
+        });
     }
 
-    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Type<E>> + '_> {
-        match self {
-            Type::Array(ar) => Box::new(std::iter::once(&mut *ar.base)),
-            Type::Tuple(tu) => Box::new(tu.items.iter_mut()),
-            Type::Function(fun) => Box::new(
-                fun.params
-                    .iter_mut()
-                    .chain(std::iter::once(&mut *fun.value)),
-            ),
-            Type::TypeVar(_) | Type::NamedType(_, None) => Box::new(std::iter::empty()),
-            Type::NamedType(_, Some(args)) => Box::new(args.iter_mut()),
-            _ => {
-                assert!(self.is_elementary());
-                Box::new(std::iter::empty())
-            }
-        }
+    /// Owned version of `rename_type_vars_with`.
+    pub fn with_renamed_vars(mut self, mapping: &HashMap<String, String>) -> Type<E> {
+        self.rename_type_vars_with(mapping);
+        self
     }
-}
 
-/// Returns iterators over all direct expressions inside this type.
-impl<R> Children<Expression<R>> for Type<Expression<R>> {
-    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+    /// Turns a function returning a function into a single function taking all
+    /// parameters at once, e.g. `int -> (fe -> bool)` into `int, fe -> bool`.
+    /// This is applied repeatedly to the return type. Non-function types are
+    /// returned unchanged.
+    /// The uncurried type is only a valid replacement if the intermediate
+    /// functions are not used on their own, i.e. the function is always called
+    /// with all arguments.
+    pub fn uncurry(&self) -> Type<E> {
         match self {
-            _ if self.is_elementary() => Box::new(empty()),
-            Type::TypeVar(_) | Type::NamedType(_, None) => Box::new(empty()),
-            Type::Array(a) => a.children(),
-            Type::Tuple(t) => t.children(),
-            Type::Function(f) => f.children(),
-            Type::NamedType(_, Some(args)) => Box::new(args.iter().flat_map(|arg| arg.children())),
-            _ => unreachable!(),
+            Type::Function(FunctionType { params, value }) => match value.uncurry() {
+                Type::Function(FunctionType {
+                    params: inner_params,
+                    value,
+                }) => Type::Function(FunctionType {
+                    params: params.iter().cloned().chain(inner_params).collect(),
+                    value,
+                }),
+                value => Type::Function(FunctionType {
+                    params: params.clone(),
+                    value: Box::new(value),
+                }),
+            },
+            t => t.clone(),
         }
     }
 
-    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+    /// Turns a function taking multiple parameters into a chain of functions
+    /// taking one parameter each, e.g. `int, fe -> bool` into `int -> (fe -> bool)`.
+    /// This is the inverse of `uncurry` for functions where each of the nested
+    /// functions takes exactly one parameter. Returns all other types unchanged.
+    pub fn curry(&self) -> Type<E> {
         match self {
-            _ if self.is_elementary() => Box::new(empty()),
-            Type::TypeVar(_) | Type::NamedType(_, None) => Box::new(empty()),
-            Type::Array(a) => a.children_mut(),
-            Type::Tuple(t) => t.children_mut(),
-            Type::Function(f) => f.children_mut(),
-            Type::NamedType(_, Some(args)) => {
-                Box::new(args.iter_mut().flat_map(|arg| arg.children_mut()))
+            Type::Function(FunctionType { params, value }) if !params.is_empty() => {
+                params.iter().rev().fold(value.curry(), |value, param| {
+                    Type::Function(FunctionType {
+                        params: vec![param.clone()],
+                        value: Box::new(value),
+                    })
+                })
             }
-            _ => unreachable!(),
+            t => t.clone(),
         }
     }
-}
-
-impl<R> Children<Expression<R>> for Type<u64> {
-    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
-        Box::new(empty())
-    }
 
-    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
-        Box::new(empty())
+    /// Returns a canonical representative of this type, such that types that only
+    /// differ in the names of their type variables or in single-element tuples
+    /// have the same canonical form.
+    pub fn canonicalize(self) -> Type<E> {
+        self.normalize().rename_type_vars()
     }
 }
 
-impl<R: Display> From<Type<Expression<R>>> for Type<u64> {
-    fn from(value: Type<Expression<R>>) -> Self {
-        match value {
-            Type::Bottom => Type::Bottom,
-            Type::Bool => Type::Bool,
-            Type::Int => Type::Int,
-            Type::Fe => Type::Fe,
-            Type::String => Type::String,
-            Type::Col => Type::Col,
-            Type::Inter => Type::Inter,
-            Type::Expr => Type::Expr,
-            Type::Array(a) => Type::Array(a.into()),
-            Type::Tuple(t) => Type::Tuple(t.into()),
-            Type::Function(f) => Type::Function(f.into()),
-            Type::TypeVar(n) => Type::TypeVar(n),
-            Type::NamedType(n, None) => Type::NamedType(n, None),
-            Type::NamedType(n, Some(args)) => {
-                Type::NamedType(n, Some(args.into_iter().map(|a| a.into()).collect()))
+impl<E: Display> Type<E> {
+    /// Checks that this type can be the declared type of a witness column,
+    /// i.e. it is `fe` or `col`, or an array of fixed length or tuple
+    /// (or record) of such types.
+    pub fn is_valid_witness_type(&self) -> Result<(), WitnessTypeError> {
+        match self {
+            Type::Fe | Type::Col => Ok(()),
+            Type::Array(ArrayType {
+                base,
+                length: Some(_),
+            }) => base.is_valid_witness_type(),
+            Type::Array(ArrayType { length: None, .. }) => {
+                Err(WitnessTypeError::UnsizedArray(self.to_string()))
+            }
+            Type::Tuple(TupleType { items }) => {
+                items.iter().try_for_each(|t| t.is_valid_witness_type())
             }
+            Type::Record(RecordType { fields }) => fields
+                .iter()
+                .try_for_each(|(_, t)| t.is_valid_witness_type()),
+            Type::Function(_) => Err(WitnessTypeError::Function(self.to_string())),
+            Type::TypeVar(name) => Err(WitnessTypeError::TypeVar(name.clone())),
+            Type::String => Err(WitnessTypeError::String),
+            Type::Bottom
+            | Type::Bool
+            | Type::Int
+            | Type::Inter
+            | Type::Expr
+            | Type::Optional(_)
+            | Type::NamedType(_, _) => Err(WitnessTypeError::Unsupported(self.to_string())),
         }
     }
 }
 
-#[derive(
-    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize, JsonSchema,
-)]
-pub struct ArrayType<E = u64> {
-    pub base: Box<Type<E>>,
-    pub length: Option<E>,
+/// The reason why a type cannot be the type of a witness column,
+/// see `Type::is_valid_witness_type`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum WitnessTypeError {
+    Function(String),
+    TypeVar(String),
+    UnsizedArray(String),
+    String,
+    Unsupported(String),
 }
 
-impl<R: Display> From<ArrayType<Expression<R>>> for ArrayType<u64> {
-    fn from(value: ArrayType<Expression<R>>) -> Self {
-        let length = value.length.as_ref().map(|l| {
-            if let Expression::Number(_, Number {value: n, type_: ty}) = l {
-                assert!(ty.is_none(), "Literal inside type name has assigned type. This should be done during analysis on the types instead.");
-                n.try_into().expect("Array length expression too large.")
-            } else {
-                panic!(
-                    "Array length expression not resolved in type name prior to conversion: {value}"
-                );
+impl Display for WitnessTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WitnessTypeError::Function(t) => {
+                write!(f, "Function type `{t}` cannot be the type of a witness column.")
+            }
+            WitnessTypeError::TypeVar(name) => write!(
+                f,
+                "Type variable `{name}` has to be resolved to a concrete type to be the type of a witness column."
+            ),
+            WitnessTypeError::UnsizedArray(t) => write!(
+                f,
+                "Array type `{t}` needs a fixed length to be the type of a witness column."
+            ),
+            WitnessTypeError::String => {
+                write!(f, "Type `string` cannot be the type of a witness column.")
+            }
+            WitnessTypeError::Unsupported(t) => {
+                write!(f, "Type `{t}` cannot be the type of a witness column.")
             }
-        });
-        ArrayType {
-            base: Box::new(Type::from(*value.base)),
-            length,
         }
     }
 }
 
-impl<R> Children<Expression<R>> for ArrayType<Expression<R>> {
-    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
-        Box::new(self.base.children().chain(self.length.as_ref()))
-    }
-
-    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
-        Box::new(self.base.children_mut().chain(self.length.as_mut()))
+impl<E: Serialize> Type<E> {
+    /// Returns the JSON representation of this type, as given by its serde
+    /// implementation (see also the `JsonSchema` of `Type`).
+    pub fn to_json_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
     }
 }
 
-#[derive(
-    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize, JsonSchema,
-)]
-pub struct TupleType<E = u64> {
-    pub items: Vec<Type<E>>,
+impl<E: DeserializeOwned> Type<E> {
+    /// Decodes a type from the JSON representation returned by `to_json_value`.
+    pub fn from_json_value(value: serde_json::Value) -> Result<Type<E>, serde_json::Error> {
+        serde_json::from_value(value)
+    }
 }
 
-impl<R> Children<Expression<R>> for TupleType<Expression<R>> {
-    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
-        Box::new(self.items.iter().flat_map(|t| t.children()))
+impl<E: Display + Clone> Type<E> {
+    /// Formats the type with as few parentheses as possible while still
+    /// being parsed back to the same type, i.e. function types are only put in
+    /// parentheses where the grammar expects a type term (array bases, tuple items,
+    /// parameters, return values, type arguments, record fields and optional types).
+    /// Single-element tuples cannot be written in the source and are formatted as `(t)`.
+    pub fn display_compact(&self) -> String {
+        self.format_compact(false)
     }
-    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
-        Box::new(self.items.iter_mut().flat_map(|t| t.children_mut()))
+
+    /// Formats the type for `display_compact`. If `term` is true, the type is
+    /// at a position where only a type term is allowed.
+    fn format_compact(&self, term: bool) -> String {
+        let terms = |types: &[Type<E>]| types.iter().map(|t| t.format_compact(true)).join(", ");
+        match self {
+            Type::Array(ArrayType { base, length }) => {
+                format!(
+                    "{}[{}]",
+                    base.format_compact(true),
+                    length.iter().format("")
+                )
+            }
+            Type::Tuple(TupleType { items }) => format!("({})", terms(items)),
+            Type::Function(FunctionType { params, value }) => {
+                let fun = format!(
+                    "{}{}-> {}",
+                    terms(params),
+                    if params.is_empty() { "" } else { " " },
+                    value.format_compact(true)
+                );
+                if term {
+                    format!("({fun})")
+                } else {
+                    fun
+                }
+            }
+            Type::Record(RecordType { fields }) => format!(
+                "{{{}}}",
+                fields
+                    .iter()
+                    .map(|(name, t)| format!("{name}: {}", t.format_compact(true)))
+                    .format(", ")
+            ),
+            Type::Optional(inner) => format!("{}?", inner.format_compact(true)),
+            Type::NamedType(name, Some(args)) => {
+                // Avoid `>>` at the end, which would be parsed as a single token.
+                let space = if matches!(args.last(), Some(Type::NamedType(_, Some(_)))) {
+                    " "
+                } else {
+                    ""
+                };
+                format!("{name}<{}{space}>", terms(args))
+            }
+            _ => self.to_string(),
+        }
     }
-}
 
-impl<R: Display> From<TupleType<Expression<R>>> for TupleType<u64> {
-    fn from(value: TupleType<Expression<R>>) -> Self {
-        TupleType {
-            items: value.items.into_iter().map(|t| t.into()).collect(),
+    /// Formats the type, but replaces all sub-types nested more than `max_depth`
+    /// levels deep by `...`, so that huge types do not blow up log messages.
+    /// For example, `(int, fe[])` with a maximum depth of 1 is formatted as `(int, ...[])`.
+    pub fn display_truncated(&self, max_depth: usize) -> String {
+        let child = |t: &Type<E>| match max_depth.checked_sub(1) {
+            None => "...".to_string(),
+            Some(depth) if t.needs_parentheses() => format!("({})", t.display_truncated(depth)),
+            Some(depth) => t.display_truncated(depth),
+        };
+        let children = |types: &[Type<E>]| types.iter().map(child).join(", ");
+        match self {
+            Type::Array(ArrayType { base, length }) => {
+                format!("{}[{}]", child(base), length.iter().format(""))
+            }
+            Type::Tuple(TupleType { items }) => format!("({})", children(items)),
+            Type::Function(FunctionType { params, value }) => format!(
+                "{}{}-> {}",
+                children(params),
+                if params.is_empty() { "" } else { " " },
+                child(value)
+            ),
+            Type::Record(RecordType { fields }) => format!(
+                "{{{}}}",
+                fields
+                    .iter()
+                    .map(|(name, t)| format!("{name}: {}", child(t)))
+                    .format(", ")
+            ),
+            Type::Optional(inner) => format!("{}?", child(inner)),
+            Type::NamedType(name, Some(args)) => {
+                let space =
+                    if max_depth > 0 && matches!(args.last(), Some(Type::NamedType(_, Some(_)))) {
+                        " "
+                    } else {
+                        ""
+                    };
+                format!("{name}<{}{space}>", children(args))
+            }
+            _ => self.to_string(),
         }
     }
 }
 
-#[derive(
-    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize, JsonSchema,
-)]
-pub struct FunctionType<E = u64> {
-    pub params: Vec<Type<E>>,
-    pub value: Box<Type<E>>,
+/// A type in canonical form (see `Type::canonicalize`), to be used as a key
+/// in maps or sets where types that are equal up to renaming should be identified.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
+pub struct CanonicalType<E = u64>(Type<E>);
+
+impl<E: Clone> From<Type<E>> for CanonicalType<E> {
+    fn from(ty: Type<E>) -> Self {
+        CanonicalType(ty.canonicalize())
+    }
 }
 
-impl<R> Children<Expression<R>> for FunctionType<Expression<R>> {
-    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
-        Box::new(
-            self.params
-                .iter()
-                .flat_map(|t| t.children())
-                .chain(self.value.children()),
-        )
+impl<E> CanonicalType<E> {
+    pub fn ty(&self) -> &Type<E> {
+        &self.0
     }
 
-    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
-        Box::new(
-            self.params
-                .iter_mut()
-                .flat_map(|t| t.children_mut())
-                .chain(self.value.children_mut()),
-        )
+    pub fn into_type(self) -> Type<E> {
+        self.0
     }
 }
 
-impl<R: Display> From<FunctionType<Expression<R>>> for FunctionType<u64> {
-    fn from(value: FunctionType<Expression<R>>) -> Self {
-        FunctionType {
-            params: value.params.into_iter().map(|t| t.into()).collect(),
-            value: Box::new((*value.value).into()),
+impl<E: PartialEq> Type<E> {
+    /// Returns true if the two types are equal when ignoring all array lengths,
+    /// i.e. `int[4]`, `int[8]` and `int[]` all have the same shape.
+    pub fn same_shape(&self, other: &Type<E>) -> bool {
+        let all_same_shape = |a: &[Type<E>], b: &[Type<E>]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(t1, t2)| t1.same_shape(t2))
+        };
+        match (self, other) {
+            (Type::Array(a1), Type::Array(a2)) => a1.base.same_shape(&a2.base),
+            (Type::Tuple(t1), Type::Tuple(t2)) => all_same_shape(&t1.items, &t2.items),
+            (Type::Function(f1), Type::Function(f2)) => {
+                all_same_shape(&f1.params, &f2.params) && f1.value.same_shape(&f2.value)
+            }
+            (Type::Record(r1), Type::Record(r2)) => {
+                r1.fields.len() == r2.fields.len()
+                    && r1
+                        .fields
+                        .iter()
+                        .zip(&r2.fields)
+                        .all(|((n1, t1), (n2, t2))| n1 == n2 && t1.same_shape(t2))
+            }
+            (Type::Optional(t1), Type::Optional(t2)) => t1.same_shape(t2),
+            (Type::NamedType(n1, None), Type::NamedType(n2, None)) => n1 == n2,
+            (Type::NamedType(n1, Some(args1)), Type::NamedType(n2, Some(args2))) => {
+                n1 == n2 && all_same_shape(args1, args2)
+            }
+            (Type::TypeVar(n1), Type::TypeVar(n2)) => n1 == n2,
+            (t1, t2) => t1.is_elementary() && t1 == t2,
         }
     }
-}
 
-impl From<FunctionType> for Type {
-    fn from(value: FunctionType) -> Self {
-        Type::Function(value)
+    /// Returns true if the two types are equal up to a consistent and injective
+    /// renaming of type variables. The renaming is recorded in `renaming`
+    /// and its inverse in `inverse_renaming`.
+    fn eq_up_to_renaming<'a>(
+        &'a self,
+        other: &'a Type<E>,
+        renaming: &mut HashMap<&'a String, &'a String>,
+        inverse_renaming: &mut HashMap<&'a String, &'a String>,
+    ) -> bool {
+        let mut all_eq = |items: &mut dyn Iterator<Item = (&'a Type<E>, &'a Type<E>)>| {
+            items.all(|(t1, t2)| t1.eq_up_to_renaming(t2, renaming, inverse_renaming))
+        };
+        match (self, other) {
+            (Type::TypeVar(n1), Type::TypeVar(n2)) => {
+                *renaming.entry(n1).or_insert(n2) == n2
+                    && *inverse_renaming.entry(n2).or_insert(n1) == n1
+            }
+            (Type::Array(a1), Type::Array(a2)) => {
+                a1.length == a2.length
+                    && a1
+                        .base
+                        .eq_up_to_renaming(&a2.base, renaming, inverse_renaming)
+            }
+            (Type::Tuple(t1), Type::Tuple(t2)) => {
+                t1.items.len() == t2.items.len() && all_eq(&mut t1.items.iter().zip(&t2.items))
+            }
+            (Type::Function(f1), Type::Function(f2)) => {
+                f1.params.len() == f2.params.len()
+                    && all_eq(
+                        &mut f1
+                            .params
+                            .iter()
+                            .zip(&f2.params)
+                            .chain(std::iter::once((&*f1.value, &*f2.value))),
+                    )
+            }
+            (Type::Record(r1), Type::Record(r2)) => {
+                r1.fields.len() == r2.fields.len()
+                    && r1
+                        .fields
+                        .iter()
+                        .zip(&r2.fields)
+                        .all(|((n1, _), (n2, _))| n1 == n2)
+                    && all_eq(
+                        &mut r1
+                            .fields
+                            .iter()
+                            .zip(&r2.fields)
+                            .map(|((_, t1), (_, t2))| (t1, t2)),
+                    )
+            }
+            (Type::Optional(t1), Type::Optional(t2)) => {
+                t1.eq_up_to_renaming(t2, renaming, inverse_renaming)
+            }
+            (Type::NamedType(n1, None), Type::NamedType(n2, None)) => n1 == n2,
+            (Type::NamedType(n1, Some(args1)), Type::NamedType(n2, Some(args2))) => {
+                n1 == n2 && args1.len() == args2.len() && all_eq(&mut args1.iter().zip(args2))
+            }
+            (t1, t2) => t1.is_elementary() && t1 == t2,
+        }
     }
-}
 
-#[derive(
-    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema, Hash,
-)]
-pub struct TypeScheme<E = u64> {
-    /// Type variables and their trait bounds.
-    pub vars: TypeBounds,
+    /// Returns true if this type is an instance of `template`, i.e. if the type
+    /// variables in `template` can be replaced by types such that it equals this type.
+    /// Repeated type variables in the template have to be replaced by the same type,
+    /// so `(T, T)` matches `(int, int)` but not `(int, fe)`.
+    /// Type variables in this type are not replaced, they only match themselves
+    /// or type variables of the template.
+    pub fn matches_template(&self, template: &Type<E>) -> bool {
+        self.matches_template_with(template, &mut HashMap::new())
+    }
+
+    fn matches_template_with<'a>(
+        &'a self,
+        template: &'a Type<E>,
+        bindings: &mut HashMap<&'a String, &'a Type<E>>,
+    ) -> bool {
+        let mut all_match = |items: &mut dyn Iterator<Item = (&'a Type<E>, &'a Type<E>)>| {
+            items.all(|(t, template)| t.matches_template_with(template, bindings))
+        };
+        match (self, template) {
+            (t, Type::TypeVar(var)) => *bindings.entry(var).or_insert(t) == t,
+            (Type::Array(a), Type::Array(template)) => {
+                a.length == template.length
+                    && a.base.matches_template_with(&template.base, bindings)
+            }
+            (Type::Tuple(t), Type::Tuple(template)) => {
+                t.items.len() == template.items.len()
+                    && all_match(&mut t.items.iter().zip(&template.items))
+            }
+            (Type::Function(f), Type::Function(template)) => {
+                f.params.len() == template.params.len()
+                    && all_match(
+                        &mut f
+                            .params
+                            .iter()
+                            .zip(&template.params)
+                            .chain(std::iter::once((&*f.value, &*template.value))),
+                    )
+            }
+            (Type::Record(r), Type::Record(template)) => {
+                r.fields.len() == template.fields.len()
+                    && r.fields
+                        .iter()
+                        .zip(&template.fields)
+                        .all(|((n1, _), (n2, _))| n1 == n2)
+                    && all_match(
+                        &mut r
+                            .fields
+                            .iter()
+                            .zip(&template.fields)
+                            .map(|((_, t1), (_, t2))| (t1, t2)),
+                    )
+            }
+            (Type::Optional(t), Type::Optional(template)) => {
+                t.matches_template_with(template, bindings)
+            }
+            (Type::NamedType(n1, None), Type::NamedType(n2, None)) => n1 == n2,
+            (Type::NamedType(n1, Some(args)), Type::NamedType(n2, Some(template_args))) => {
+                n1 == n2
+                    && args.len() == template_args.len()
+                    && all_match(&mut args.iter().zip(template_args))
+            }
+            (t1, t2) => t1.is_elementary() && t1 == t2,
+        }
+    }
+}
+
+impl<E: PartialEq + Clone> Type<E> {
+    /// Returns None if the two types are equal and otherwise the location of the
+    /// first (outermost, leftmost) difference together with the differing sub-types.
+    pub fn diff(&self, other: &Type<E>) -> Option<TypeDiff<E>> {
+        self.diff_at(other, &mut vec![])
+    }
+
+    fn diff_at(&self, other: &Type<E>, path: &mut Vec<TypePathStep>) -> Option<TypeDiff<E>> {
+        let pairs = match (self, other) {
+            (Type::Array(a1), Type::Array(a2)) if a1.length != a2.length => None,
+            _ => self.child_pairs(other),
+        };
+        match pairs {
+            Some(pairs) => pairs.into_iter().find_map(|(step, t1, t2)| {
+                path.push(step);
+                let diff = t1.diff_at(t2, path);
+                path.pop();
+                diff
+            }),
+            None if self == other => None,
+            None => Some(TypeDiff {
+                path: TypePath(path.clone()),
+                left: self.clone(),
+                right: other.clone(),
+            }),
+        }
+    }
+
+    /// If the two types have the same structure, returns the pairs of corresponding
+    /// leaves, i.e. elementary types, type variables and named types without type
+    /// arguments, in order. Array lengths are ignored, see `same_shape`.
+    /// Otherwise returns the location of the first (outermost, leftmost) place
+    /// where the structures diverge.
+    pub fn zip<'a>(
+        &'a self,
+        other: &'a Type<E>,
+    ) -> Result<Vec<(&'a Type<E>, &'a Type<E>)>, ShapeMismatch<E>> {
+        let mut leaves = vec![];
+        self.zip_at(other, &mut vec![], &mut leaves)?;
+        Ok(leaves)
+    }
+
+    fn zip_at<'a>(
+        &'a self,
+        other: &'a Type<E>,
+        path: &mut Vec<TypePathStep>,
+        leaves: &mut Vec<(&'a Type<E>, &'a Type<E>)>,
+    ) -> Result<(), ShapeMismatch<E>> {
+        let is_leaf = |t: &Type<E>| {
+            t.is_elementary() || matches!(t, Type::TypeVar(_) | Type::NamedType(_, None))
+        };
+        match self.child_pairs(other) {
+            Some(pairs) => pairs.into_iter().try_for_each(|(step, t1, t2)| {
+                path.push(step);
+                t1.zip_at(t2, path, leaves)?;
+                path.pop();
+                Ok(())
+            }),
+            None if is_leaf(self) && is_leaf(other) => {
+                leaves.push((self, other));
+                Ok(())
+            }
+            None => Err(ShapeMismatch {
+                path: TypePath(path.clone()),
+                left: self.clone(),
+                right: other.clone(),
+            }),
+        }
+    }
+
+    /// Returns the pairs of corresponding direct sub-types if the two types are
+    /// composite types of the same kind and arity, ignoring array lengths.
+    fn child_pairs<'a>(
+        &'a self,
+        other: &'a Type<E>,
+    ) -> Option<Vec<(TypePathStep, &'a Type<E>, &'a Type<E>)>> {
+        match (self, other) {
+            (Type::Array(a1), Type::Array(a2)) => {
+                Some(vec![(TypePathStep::ArrayBase, &*a1.base, &*a2.base)])
+            }
+            (Type::Optional(t1), Type::Optional(t2)) => {
+                Some(vec![(TypePathStep::OptionalInner, &**t1, &**t2)])
+            }
+            (Type::Tuple(t1), Type::Tuple(t2)) if t1.items.len() == t2.items.len() => Some(
+                t1.items
+                    .iter()
+                    .zip(&t2.items)
+                    .enumerate()
+                    .map(|(i, (t1, t2))| (TypePathStep::TupleItem(i), t1, t2))
+                    .collect(),
+            ),
+            (Type::Function(f1), Type::Function(f2)) if f1.params.len() == f2.params.len() => Some(
+                f1.params
+                    .iter()
+                    .zip(&f2.params)
+                    .enumerate()
+                    .map(|(i, (t1, t2))| (TypePathStep::Param(i), t1, t2))
+                    .chain(std::iter::once((
+                        TypePathStep::ReturnType,
+                        &*f1.value,
+                        &*f2.value,
+                    )))
+                    .collect(),
+            ),
+            (Type::Record(r1), Type::Record(r2))
+                if r1.fields.len() == r2.fields.len()
+                    && r1
+                        .fields
+                        .iter()
+                        .zip(&r2.fields)
+                        .all(|((n1, _), (n2, _))| n1 == n2) =>
+            {
+                Some(
+                    r1.fields
+                        .iter()
+                        .zip(&r2.fields)
+                        .map(|((n, t1), (_, t2))| (TypePathStep::RecordField(n.clone()), t1, t2))
+                        .collect(),
+                )
+            }
+            (Type::NamedType(n1, Some(args1)), Type::NamedType(n2, Some(args2)))
+                if n1 == n2 && args1.len() == args2.len() =>
+            {
+                Some(
+                    args1
+                        .iter()
+                        .zip(args2)
+                        .enumerate()
+                        .map(|(i, (t1, t2))| (TypePathStep::TypeArg(i), t1, t2))
+                        .collect(),
+                )
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A step from a type to one of its direct sub-types.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TypePathStep {
+    /// The element type of an array.
+    ArrayBase,
+    /// The `i`th item of a tuple.
+    TupleItem(usize),
+    /// The `i`th parameter of a function.
+    Param(usize),
+    /// The return type of a function.
+    ReturnType,
+    /// The field of a record with the given name.
+    RecordField(String),
+    /// The `i`th type argument of a named type.
+    TypeArg(usize),
+    /// The inner type of an optional type.
+    OptionalInner,
+}
+
+/// The location of a sub-type inside a type, as the sequence of steps from
+/// the outermost type to the sub-type. The empty path denotes the type itself.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct TypePath(pub Vec<TypePathStep>);
+
+impl Display for TypePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "the type itself");
+        }
+        let steps = self.0.iter().map(|step| match step {
+            TypePathStep::ArrayBase => "array element".to_string(),
+            TypePathStep::TupleItem(i) => format!("tuple item {i}"),
+            TypePathStep::Param(i) => format!("parameter {i}"),
+            TypePathStep::ReturnType => "return type".to_string(),
+            TypePathStep::RecordField(name) => format!("field {name}"),
+            TypePathStep::TypeArg(i) => format!("type argument {i}"),
+            TypePathStep::OptionalInner => "optional value".to_string(),
+        });
+        write!(f, "{}", steps.format(" / "))
+    }
+}
+
+/// The first difference between two types, see `Type::diff`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TypeDiff<E = u64> {
+    pub path: TypePath,
+    pub left: Type<E>,
+    pub right: Type<E>,
+}
+
+/// The place where the structures of two types diverge, see `Type::zip`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ShapeMismatch<E = u64> {
+    pub path: TypePath,
+    pub left: Type<E>,
+    pub right: Type<E>,
+}
+
+impl<E> Type<E> {
+    /// Calls `f` on this type and all its direct and indirect sub-types, in pre-order.
+    pub fn walk_mut(&mut self, f: &mut impl FnMut(&mut Type<E>)) {
+        f(self);
+        self.children_mut().for_each(|t| t.walk_mut(f));
+    }
+
+    /// Renames every type variable to the name returned by `fresh`, which is
+    /// called once per distinct type variable, so that all occurrences of a type
+    /// variable are renamed to the same new name.
+    /// This is used to avoid capturing type variables of the call site when
+    /// inlining a polymorphic definition.
+    pub fn freshen_vars(&mut self, fresh: &mut impl FnMut(&str) -> String) {
+        let mut mapping: HashMap<String, String> = HashMap::new();
+        self.walk_mut(&mut |t| {
+            if let Type::TypeVar(n) = t {
+                let new_name = mapping.entry(n.clone()).or_insert_with(|| fresh(n)).clone();
+                *n = new_name;
+            }
+        });
+    }
+
+    /// Sets the lengths of all arrays in this type to unknown.
+    pub fn forget_array_lengths(&mut self) {
+        self.walk_mut(&mut |t| {
+            if let Type::Array(a) = t {
+                a.length = None;
+            }
+        });
+    }
+
+    /// Transforms this type by applying `f` to all its sub-types in post-order,
+    /// i.e. `f` is applied to a type after all its components have been transformed.
+    pub fn map(mut self, f: &mut impl FnMut(Type<E>) -> Type<E>) -> Type<E> {
+        self.children_mut().for_each(|t| {
+            let child = std::mem::replace(t, Type::Bottom);
+            *t = child.map(f);
+        });
+        f(self)
+    }
+
+    /// Like `map`, but also passes the path from this type to the transformed
+    /// sub-type to `f`, which allows transformations that depend on the position
+    /// of the sub-type, e.g. inside or outside of function parameters.
+    pub fn fold_with_path(self, f: &mut impl FnMut(&TypePath, Type<E>) -> Type<E>) -> Type<E> {
+        self.fold_with_path_at(&mut TypePath::default(), f)
+    }
+
+    fn fold_with_path_at(
+        mut self,
+        path: &mut TypePath,
+        f: &mut impl FnMut(&TypePath, Type<E>) -> Type<E>,
+    ) -> Type<E> {
+        for (step, t) in self.children_with_steps_mut() {
+            let child = std::mem::replace(t, Type::Bottom);
+            path.0.push(step);
+            *t = child.fold_with_path_at(path, f);
+            path.0.pop();
+        }
+        f(path, self)
+    }
+
+    /// Checks that the bottom type `!` only occurs in positions where it means
+    /// divergence. Other occurrences usually mean that type inference did not
+    /// resolve a type, e.g. of a branch.
+    ///
+    /// `!` means divergence as the type itself and as the return type of a function
+    /// (also of a nested function). In all other positions (tuple items, array elements,
+    /// record fields, function parameters, optional values and type arguments), it is
+    /// considered unresolved and the path to its first such occurrence is returned as error.
+    /// Otherwise, the type is returned unchanged.
+    pub fn simplify_bottom(mut self) -> Result<Type<E>, TypePath> {
+        let mut path = vec![];
+        if self.find_unresolved_bottom(&mut path, true) {
+            Err(TypePath(path))
+        } else {
+            Ok(self)
+        }
+    }
+
+    /// Returns true if this type contains an unresolved `!` (see `simplify_bottom`)
+    /// and leaves the path to it in `path`.
+    fn find_unresolved_bottom(&mut self, path: &mut Vec<TypePathStep>, may_diverge: bool) -> bool {
+        if let Type::Bottom = self {
+            return !may_diverge;
+        }
+        for (step, child) in self.children_with_steps_mut() {
+            let may_diverge = step == TypePathStep::ReturnType;
+            path.push(step);
+            if child.find_unresolved_bottom(path, may_diverge) {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+
+    /// Returns the direct sub-types of this type together with the
+    /// step from this type to the sub-type.
+    fn children_with_steps_mut(&mut self) -> Vec<(TypePathStep, &mut Type<E>)> {
+        match self {
+            Type::Array(ArrayType { base, .. }) => vec![(TypePathStep::ArrayBase, base.as_mut())],
+            Type::Tuple(TupleType { items }) => items
+                .iter_mut()
+                .enumerate()
+                .map(|(i, t)| (TypePathStep::TupleItem(i), t))
+                .collect(),
+            Type::Function(FunctionType { params, value }) => params
+                .iter_mut()
+                .enumerate()
+                .map(|(i, t)| (TypePathStep::Param(i), t))
+                .chain(std::iter::once((TypePathStep::ReturnType, value.as_mut())))
+                .collect(),
+            Type::Record(RecordType { fields }) => fields
+                .iter_mut()
+                .map(|(name, t)| (TypePathStep::RecordField(name.clone()), t))
+                .collect(),
+            Type::Optional(inner) => vec![(TypePathStep::OptionalInner, inner.as_mut())],
+            Type::NamedType(_, Some(args)) => args
+                .iter_mut()
+                .enumerate()
+                .map(|(i, t)| (TypePathStep::TypeArg(i), t))
+                .collect(),
+            Type::Bottom
+            | Type::Bool
+            | Type::Int
+            | Type::Fe
+            | Type::String
+            | Type::Col
+            | Type::Inter
+            | Type::Expr
+            | Type::TypeVar(_)
+            | Type::NamedType(_, None) => vec![],
+        }
+    }
+
+    /// Replaces all tuple types with a single component by that component.
+    pub fn normalize(self) -> Type<E> {
+        self.map(&mut |t| match t {
+            Type::Tuple(TupleType { mut items }) if items.len() == 1 => items.pop().unwrap(),
+            t => t,
+        })
+    }
+
+    /// Returns tuple types unchanged and wraps all other types in a
+    /// single-element tuple. See `TupleType::into_type` for the inverse.
+    pub fn into_tuple(self) -> TupleType<E> {
+        match self {
+            Type::Tuple(tuple) => tuple,
+            t => TupleType { items: vec![t] },
+        }
+    }
+
+    /// Applies `f` to all elementary types contained in this type,
+    /// leaving all other types intact.
+    pub fn map_elementary(self, f: impl Fn(Type<E>) -> Type<E>) -> Type<E> {
+        self.map(&mut |t| if t.is_elementary() { f(t) } else { t })
+    }
+
+    /// Returns the parameter types and the return type if this is a function type
+    /// and returns the type unchanged as error otherwise.
+    pub fn split_function(self) -> Result<(Vec<Type<E>>, Type<E>), Type<E>> {
+        match self {
+            Type::Function(FunctionType { params, value }) => Ok((params, *value)),
+            t => Err(t),
+        }
+    }
+
+    /// Replaces all occurrences of `col` by `expr`.
+    pub fn promote_cols(self) -> Type<E> {
+        self.map(&mut |t| match t {
+            Type::Col => Type::Expr,
+            t => t,
+        })
+    }
+
+    /// Returns all elementary types contained in this type, from left to right.
+    /// Container types and type variables are not included, only their
+    /// elementary components.
+    pub fn leaves(&self) -> Box<dyn Iterator<Item = &Type<E>> + '_> {
+        if self.is_elementary() {
+            Box::new(std::iter::once(self))
+        } else {
+            Box::new(self.children().flat_map(|t| t.leaves()))
+        }
+    }
+
+    /// Returns the number of distinct type variables in this type. Equivalent to
+    /// `contained_type_vars().count()`, but avoids the boxed iterators and the hash set.
+    pub fn num_type_vars(&self) -> usize {
+        let mut vars = vec![];
+        self.collect_type_vars(&mut vars);
+        vars.sort_unstable();
+        vars.dedup();
+        vars.len()
+    }
+
+    fn collect_type_vars<'a>(&'a self, vars: &mut Vec<&'a String>) {
+        match self {
+            Type::TypeVar(n) => vars.push(n),
+            _ => self.children().for_each(|t| t.collect_type_vars(vars)),
+        }
+    }
+
+    fn contained_type_vars_with_repetitions(&self) -> Box<dyn Iterator<Item = &String> + '_> {
+        match self {
+            Type::TypeVar(n) => Box::new(std::iter::once(n)),
+            _ => Box::new(
+                self.children()
+                    .flat_map(|t| t.contained_type_vars_with_repetitions()),
+            ),
+        }
+    }
+}
+
+impl Type {
+    /// Returns how a value of this type is serialized into field elements,
+    /// or None if values of this type cannot be serialized that way.
+    ///
+    /// Values of the types `bool`, `int`, `fe`, `col`, `inter` and `expr` are a single
+    /// field element. Tuples and records store their components one after the other
+    /// and arrays need to have a known length.
+    /// Function types, strings, the bottom type, optional types, named types and
+    /// type variables are not representable.
+    pub fn field_layout(&self) -> Option<FieldLayout> {
+        match self {
+            Type::Bool | Type::Int | Type::Fe | Type::Col | Type::Inter | Type::Expr => {
+                Some(FieldLayout::Element)
+            }
+            Type::Array(ArrayType {
+                base,
+                length: Some(length),
+            }) => {
+                let element = base.field_layout()?;
+                Some(FieldLayout::Array {
+                    stride: element.size(),
+                    element: Box::new(element),
+                    length: *length,
+                })
+            }
+            Type::Tuple(TupleType { items }) => FieldLayout::sequence(items.iter()),
+            Type::Record(RecordType { fields }) => {
+                FieldLayout::sequence(fields.iter().map(|(_, t)| t))
+            }
+            Type::Bottom
+            | Type::String
+            | Type::Array(ArrayType { length: None, .. })
+            | Type::Function(_)
+            | Type::Optional(_)
+            | Type::TypeVar(_)
+            | Type::NamedType(_, _) => None,
+        }
+    }
+
+    /// Returns true if all values of this type can be represented by elements of the field `F`,
+    /// which is what backends over `F` can handle.
+    ///
+    /// The types `bool`, `fe`, `col`, `inter` and `expr` are always representable, as well as
+    /// arrays, tuples and records of representable types. Arbitrary precision integers,
+    /// strings and all other types are not.
+    /// Only types with a fixed bit width would depend on the size of the field,
+    /// and there are none yet, so the result is currently the same for all fields.
+    pub fn is_representable_in_field<F: FieldElement>(&self) -> bool {
+        match self {
+            Type::Bool | Type::Fe | Type::Col | Type::Inter | Type::Expr => true,
+            Type::Array(ArrayType { base, .. }) => base.is_representable_in_field::<F>(),
+            Type::Tuple(TupleType { items }) => {
+                items.iter().all(|t| t.is_representable_in_field::<F>())
+            }
+            Type::Record(RecordType { fields }) => fields
+                .iter()
+                .all(|(_, t)| t.is_representable_in_field::<F>()),
+            Type::Bottom
+            | Type::Int
+            | Type::String
+            | Type::Function(_)
+            | Type::Optional(_)
+            | Type::TypeVar(_)
+            | Type::NamedType(_, _) => false,
+        }
+    }
+
+    /// Formats this type as usual, but annotates types that contain field elements
+    /// with the name of the field `F`, e.g. `fe (Goldilocks)` or `fe[2] (Goldilocks)`.
+    pub fn display_with_field<F: FieldElement>(&self) -> String {
+        if self.contains_fe() {
+            match F::known_field() {
+                Some(field) => format!("{self} ({field})"),
+                None => format!("{self} (unknown field)"),
+            }
+        } else {
+            self.to_string()
+        }
+    }
+
+    /// Builds the nested array type with the given base type and dimensions,
+    /// in the order they are written, i.e. `nested_array(Type::Int, &[2, 3])` is `int[2][3]`.
+    pub fn nested_array(base: Type, dims: &[u64]) -> Type {
+        dims.iter().fold(base, |base, &length| {
+            Type::Array(ArrayType {
+                base: Box::new(base),
+                length: Some(length),
+            })
+        })
+    }
+
+    /// The inverse of `nested_array`: Returns the innermost non-array type
+    /// and the dimensions of the nested arrays around it, in the order they are
+    /// written. Returns the type itself and no dimensions if it is not an array.
+    pub fn array_dims(&self) -> (Type, Vec<Option<u64>>) {
+        let mut dims = vec![];
+        let mut ty = self;
+        while let Type::Array(ArrayType { base, length }) = ty {
+            dims.push(*length);
+            ty = base;
+        }
+        dims.reverse();
+        (ty.clone(), dims)
+    }
+
+    /// Returns the type a value of this type has when it is used as part of an
+    /// algebraic expression:
+    /// - `fe`, `col`, `inter` and `expr` become `expr`,
+    /// - arrays of columns and intermediate columns become `expr[]`, forgetting the
+    ///   length as for references to such arrays,
+    /// - all other types are returned unchanged, they cannot be used as algebraic
+    ///   expressions.
+    ///
+    /// Numeric literals are constant algebraic expressions in these contexts, see
+    /// `coerce_literal`.
+    pub fn as_expr_context(&self) -> Type {
+        match self {
+            Type::Fe | Type::Col | Type::Inter | Type::Expr => Type::Expr,
+            Type::Array(ArrayType { base, .. })
+                if matches!(base.as_ref(), Type::Col | Type::Inter) =>
+            {
+                Type::Array(ArrayType {
+                    base: Box::new(Type::Expr),
+                    length: None,
+                })
+            }
+            t => t.clone(),
+        }
+    }
+
+    /// Replaces the length of every array in this type (including nested arrays)
+    /// by the result of `f` applied to it.
+    pub fn map_array_lengths(&mut self, f: impl Fn(Option<u64>) -> Option<u64>) {
+        self.walk_mut(&mut |t| {
+            if let Type::Array(a) = t {
+                a.length = f(a.length);
+            }
+        });
+    }
+
+    /// Returns the length if this is an array of columns of known length, i.e. `col[n]`.
+    pub fn as_column_array(&self) -> Option<u64> {
+        match self {
+            Type::Array(ArrayType {
+                base,
+                length: Some(length),
+            }) if **base == Type::Col => Some(*length),
+            _ => None,
+        }
+    }
+
+    /// Returns the most specific type that values of both `self` and `other` can be
+    /// used as, e.g. the type of a conditional with branches of these types:
+    /// - equal types have themselves as common supertype,
+    /// - the bottom type `!` has the other type as common supertype,
+    /// - `fe`, `col`, `inter` and `expr` have `expr` as common supertype,
+    ///   since they can all be used as algebraic expressions (see `as_expr_context`),
+    /// - arrays, tuples, records and optional types that have the same structure have
+    ///   the element-wise common supertype, where arrays of different or unknown
+    ///   length have unknown length.
+    ///
+    /// All other types, in particular `int` and `fe`, do not have a common supertype.
+    /// The error contains the innermost types without common supertype.
+    pub fn common_supertype(&self, other: &Type) -> Result<Type, NoCommonType> {
+        let no_common_type = || NoCommonType {
+            left: self.clone(),
+            right: other.clone(),
+        };
+        let all = |items1: &[Type], items2: &[Type]| -> Result<Vec<Type>, NoCommonType> {
+            items1
+                .iter()
+                .zip(items2)
+                .map(|(t1, t2)| t1.common_supertype(t2))
+                .collect()
+        };
+        match (self, other) {
+            (t1, t2) if t1 == t2 => Ok(t1.clone()),
+            (Type::Bottom, t) | (t, Type::Bottom) => Ok(t.clone()),
+            (
+                Type::Fe | Type::Col | Type::Inter | Type::Expr,
+                Type::Fe | Type::Col | Type::Inter | Type::Expr,
+            ) => Ok(Type::Expr),
+            (Type::Array(a1), Type::Array(a2)) => Ok(Type::Array(ArrayType {
+                base: Box::new(a1.base.common_supertype(&a2.base)?),
+                length: if a1.length == a2.length {
+                    a1.length
+                } else {
+                    None
+                },
+            })),
+            (Type::Tuple(t1), Type::Tuple(t2)) if t1.items.len() == t2.items.len() => {
+                Ok(Type::Tuple(TupleType {
+                    items: all(&t1.items, &t2.items)?,
+                }))
+            }
+            (Type::Record(r1), Type::Record(r2))
+                if r1.fields.len() == r2.fields.len()
+                    && r1
+                        .fields
+                        .iter()
+                        .zip(&r2.fields)
+                        .all(|((n1, _), (n2, _))| n1 == n2) =>
+            {
+                Ok(Type::Record(RecordType {
+                    fields: r1
+                        .fields
+                        .iter()
+                        .zip(&r2.fields)
+                        .map(|((name, t1), (_, t2))| {
+                            t1.common_supertype(t2).map(|t| (name.clone(), t))
+                        })
+                        .collect::<Result<_, _>>()?,
+                }))
+            }
+            (Type::Optional(t1), Type::Optional(t2)) => {
+                Ok(Type::Optional(Box::new(t1.common_supertype(t2)?)))
+            }
+            _ => Err(no_common_type()),
+        }
+    }
+
+    /// Sets the length of this array type if it is not yet known.
+    /// Returns an error if this is not an array type or if the array
+    /// already has a different length.
+    /// Nested arrays can be handled by calling this function through `walk_mut`.
+    pub fn set_array_length(&mut self, length: u64) -> Result<(), String> {
+        match self {
+            Type::Array(ArrayType {
+                length: l @ None, ..
+            }) => {
+                *l = Some(length);
+                Ok(())
+            }
+            Type::Array(ArrayType {
+                length: Some(l), ..
+            }) => {
+                let l = *l;
+                if l == length {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "Cannot set the length of array type {self} to {length}, it already has length {l}."
+                    ))
+                }
+            }
+            _ => Err(format!("Type {self} is not an array type.")),
+        }
+    }
+
+    /// Returns the Rust type that corresponds to this type, to be used in
+    /// generated bindings, or None if there is no such type.
+    ///
+    /// Field elements are mapped to `u64` and integers to `num_bigint::BigInt`.
+    /// Arrays of known length become Rust arrays, other arrays become vectors.
+    /// Records are mapped to tuples of their fields in declaration order and
+    /// functions are mapped to function pointers.
+    /// Columns, intermediate columns, algebraic expressions, the bottom type,
+    /// named types and type variables do not have a Rust analog.
+    pub fn to_rust_type_string(&self) -> Option<String> {
+        let list = |types: &mut dyn Iterator<Item = &Type>| {
+            types
+                .map(|t| t.to_rust_type_string())
+                .collect::<Option<Vec<_>>>()
+        };
+        let tuple = |items: Vec<String>| match items.as_slice() {
+            [item] => format!("({item},)"),
+            _ => format!("({})", items.join(", ")),
+        };
+        Some(match self {
+            Type::Bool => "bool".to_string(),
+            Type::Int => "num_bigint::BigInt".to_string(),
+            Type::Fe => "u64".to_string(),
+            Type::String => "String".to_string(),
+            Type::Array(ArrayType {
+                base,
+                length: Some(length),
+            }) => format!("[{}; {length}]", base.to_rust_type_string()?),
+            Type::Array(ArrayType { base, length: None }) => {
+                format!("Vec<{}>", base.to_rust_type_string()?)
+            }
+            Type::Tuple(TupleType { items }) => tuple(list(&mut items.iter())?),
+            Type::Record(RecordType { fields }) => tuple(list(&mut fields.iter().map(|(_, t)| t))?),
+            Type::Function(FunctionType { params, value }) => format!(
+                "fn({}) -> {}",
+                list(&mut params.iter())?.join(", "),
+                value.to_rust_type_string()?
+            ),
+            Type::Optional(inner) => format!("Option<{}>", inner.to_rust_type_string()?),
+            Type::Bottom
+            | Type::Col
+            | Type::Inter
+            | Type::Expr
+            | Type::TypeVar(_)
+            | Type::NamedType(_, _) => return None,
+        })
+    }
+
+    /// Unifies this type, which is the type that was found, with the type
+    /// `expected` and returns the substitutions for the type variables
+    /// that make the two types equal.
+    ///
+    /// In contrast to the symmetric unification, errors report which of the
+    /// two (sub-)types was expected and which one was found, also if the
+    /// mismatch is nested deep inside the types.
+    pub fn check_against(&self, expected: &Type) -> Result<HashMap<String, Type>, TypeMismatch> {
+        let mut substitutions = HashMap::new();
+        match self.unify(expected, &mut substitutions) {
+            Ok(()) => Ok(substitutions),
+            Err((mut found, mut expected)) => {
+                found.substitute_type_vars(&substitutions);
+                expected.substitute_type_vars(&substitutions);
+                Err(TypeMismatch { expected, found })
+            }
+        }
+    }
+
+    /// Symmetric unification of `self` and `other`, extending `substitutions`.
+    /// The substitutions are kept idempotent, i.e. no replacement contains
+    /// a type variable that is substituted itself.
+    /// On failure, returns the innermost pair of types that could not be
+    /// unified, in the order (self, other).
+    fn unify(
+        &self,
+        other: &Type,
+        substitutions: &mut HashMap<String, Type>,
+    ) -> Result<(), (Type, Type)> {
+        if let Type::TypeVar(n) = self {
+            if let Some(t) = substitutions.get(n).cloned() {
+                return t.unify(other, substitutions);
+            }
+        }
+        if let Type::TypeVar(n) = other {
+            if let Some(t) = substitutions.get(n).cloned() {
+                return self.unify(&t, substitutions);
+            }
+        }
+        let mismatch = || (self.clone(), other.clone());
+        let mut all_unify = |items: &mut dyn Iterator<Item = (&Type, &Type)>| {
+            items.try_for_each(|(t1, t2)| t1.unify(t2, substitutions))
+        };
+        match (self, other) {
+            (Type::TypeVar(n1), Type::TypeVar(n2)) if n1 == n2 => Ok(()),
+            (Type::TypeVar(n), t) | (t, Type::TypeVar(n)) => {
+                let mut t = t.clone();
+                t.substitute_type_vars(substitutions);
+                if t.contains_type_var(n) {
+                    return Err(mismatch());
+                }
+                let new_substitution = [(n.clone(), t.clone())].into_iter().collect();
+                for replacement in substitutions.values_mut() {
+                    replacement.substitute_type_vars(&new_substitution);
+                }
+                substitutions.insert(n.clone(), t);
+                Ok(())
+            }
+            (Type::Bottom, _) | (_, Type::Bottom) => Ok(()),
+            (Type::Array(a1), Type::Array(a2)) => match (a1.length, a2.length) {
+                (Some(l1), Some(l2)) if l1 != l2 => Err(mismatch()),
+                _ => a1.base.unify(&a2.base, substitutions),
+            },
+            (Type::Tuple(t1), Type::Tuple(t2)) if t1.items.len() == t2.items.len() => {
+                all_unify(&mut t1.items.iter().zip(&t2.items))
+            }
+            (Type::Function(f1), Type::Function(f2)) if f1.params.len() == f2.params.len() => {
+                all_unify(
+                    &mut f1
+                        .params
+                        .iter()
+                        .zip(&f2.params)
+                        .chain(std::iter::once((&*f1.value, &*f2.value))),
+                )
+            }
+            (Type::Record(r1), Type::Record(r2))
+                if r1.fields.len() == r2.fields.len()
+                    && r1
+                        .fields
+                        .iter()
+                        .zip(&r2.fields)
+                        .all(|((n1, _), (n2, _))| n1 == n2) =>
+            {
+                all_unify(
+                    &mut r1
+                        .fields
+                        .iter()
+                        .zip(&r2.fields)
+                        .map(|((_, t1), (_, t2))| (t1, t2)),
+                )
+            }
+            (Type::Optional(t1), Type::Optional(t2)) => t1.unify(t2, substitutions),
+            (Type::NamedType(n1, None), Type::NamedType(n2, None)) if n1 == n2 => Ok(()),
+            (Type::NamedType(n1, Some(args1)), Type::NamedType(n2, Some(args2)))
+                if n1 == n2 && args1.len() == args2.len() =>
+            {
+                all_unify(&mut args1.iter().zip(args2))
+            }
+            (t1, t2) if t1.is_elementary() && t1 == t2 => Ok(()),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+/// The error returned by `Type::check_against`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TypeMismatch {
+    pub expected: Type,
+    pub found: Type,
+}
+
+impl Display for TypeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Expected `{}`, found `{}`", self.expected, self.found)
+    }
+}
+
+/// The layout of a value as a sequence of field elements, see `Type::field_layout`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum FieldLayout {
+    /// A single field element.
+    Element,
+    /// Components stored one after the other, given as offset and layout of each component.
+    Sequence(Vec<(usize, FieldLayout)>),
+    /// `length` elements of the same layout, `stride` field elements apart.
+    Array {
+        element: Box<FieldLayout>,
+        stride: usize,
+        length: u64,
+    },
+}
+
+impl FieldLayout {
+    /// The number of field elements a value with this layout occupies.
+    pub fn size(&self) -> usize {
+        match self {
+            FieldLayout::Element => 1,
+            FieldLayout::Sequence(components) => components
+                .last()
+                .map(|(offset, layout)| offset + layout.size())
+                .unwrap_or(0),
+            FieldLayout::Array { stride, length, .. } => stride * (*length as usize),
+        }
+    }
+
+    fn sequence<'a>(types: impl Iterator<Item = &'a Type>) -> Option<FieldLayout> {
+        let mut offset = 0;
+        let components = types
+            .map(|t| {
+                let layout = t.field_layout()?;
+                let component = (offset, layout);
+                offset += component.1.size();
+                Some(component)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(FieldLayout::Sequence(components))
+    }
+}
+
+/// Returns iterators over all direct sub-types for this type.
+impl<E> Children<Type<E>> for Type<E> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Type<E>> + '_> {
+        match self {
+            Type::Array(ar) => Box::new(std::iter::once(&*ar.base)),
+            Type::Tuple(tu) => Box::new(tu.items.iter()),
+            Type::Function(fun) => Box::new(fun.params.iter().chain(std::iter::once(&*fun.value))),
+            Type::Record(rec) => Box::new(rec.fields.iter().map(|(_, t)| t)),
+            Type::Optional(inner) => Box::new(std::iter::once(&**inner)),
+            Type::TypeVar(_) | Type::NamedType(_, None) => Box::new(std::iter::empty()),
+            Type::NamedType(_, Some(args)) => Box::new(args.iter()),
+            _ => {
+                assert!(self.is_elementary());
+                Box::new(std::iter::empty())
+            }
+        }
+    }
+
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Type<E>> + '_> {
+        match self {
+            Type::Array(ar) => Box::new(std::iter::once(&mut *ar.base)),
+            Type::Tuple(tu) => Box::new(tu.items.iter_mut()),
+            Type::Function(fun) => Box::new(
+                fun.params
+                    .iter_mut()
+                    .chain(std::iter::once(&mut *fun.value)),
+            ),
+            Type::Record(rec) => Box::new(rec.fields.iter_mut().map(|(_, t)| t)),
+            Type::Optional(inner) => Box::new(std::iter::once(&mut **inner)),
+            Type::TypeVar(_) | Type::NamedType(_, None) => Box::new(std::iter::empty()),
+            Type::NamedType(_, Some(args)) => Box::new(args.iter_mut()),
+            _ => {
+                assert!(self.is_elementary());
+                Box::new(std::iter::empty())
+            }
+        }
+    }
+}
+
+/// Returns iterators over all direct expressions inside this type.
+impl<R> Children<Expression<R>> for Type<Expression<R>> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        match self {
+            _ if self.is_elementary() => Box::new(empty()),
+            Type::TypeVar(_) | Type::NamedType(_, None) => Box::new(empty()),
+            Type::Array(a) => a.children(),
+            Type::Tuple(t) => t.children(),
+            Type::Function(f) => f.children(),
+            Type::Record(r) => r.children(),
+            Type::Optional(inner) => inner.children(),
+            Type::NamedType(_, Some(args)) => Box::new(args.iter().flat_map(|arg| arg.children())),
+            _ => unreachable!(),
+        }
+    }
+
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        match self {
+            _ if self.is_elementary() => Box::new(empty()),
+            Type::TypeVar(_) | Type::NamedType(_, None) => Box::new(empty()),
+            Type::Array(a) => a.children_mut(),
+            Type::Tuple(t) => t.children_mut(),
+            Type::Function(f) => f.children_mut(),
+            Type::Record(r) => r.children_mut(),
+            Type::Optional(inner) => inner.children_mut(),
+            Type::NamedType(_, Some(args)) => {
+                Box::new(args.iter_mut().flat_map(|arg| arg.children_mut()))
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<R> Children<Expression<R>> for Type<u64> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        Box::new(empty())
+    }
+
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        Box::new(empty())
+    }
+}
+
+impl<R: Display> From<Type<Expression<R>>> for Type<u64> {
+    fn from(value: Type<Expression<R>>) -> Self {
+        match value {
+            Type::Bottom => Type::Bottom,
+            Type::Bool => Type::Bool,
+            Type::Int => Type::Int,
+            Type::Fe => Type::Fe,
+            Type::String => Type::String,
+            Type::Col => Type::Col,
+            Type::Inter => Type::Inter,
+            Type::Expr => Type::Expr,
+            Type::Array(a) => Type::Array(a.into()),
+            Type::Tuple(t) => Type::Tuple(t.into()),
+            Type::Function(f) => Type::Function(f.into()),
+            Type::Record(r) => Type::Record(r.into()),
+            Type::Optional(inner) => Type::Optional(Box::new((*inner).into())),
+            Type::TypeVar(n) => Type::TypeVar(n),
+            Type::NamedType(n, None) => Type::NamedType(n, None),
+            Type::NamedType(n, Some(args)) => {
+                Type::NamedType(n, Some(args.into_iter().map(|a| a.into()).collect()))
+            }
+        }
+    }
+}
+
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub struct ArrayType<E = u64> {
+    pub base: Box<Type<E>>,
+    pub length: Option<E>,
+}
+
+impl<R: Display> From<ArrayType<Expression<R>>> for ArrayType<u64> {
+    fn from(value: ArrayType<Expression<R>>) -> Self {
+        let length = value.length.as_ref().map(|l| {
+            if let Expression::Number(_, Number {value: n, type_: ty}) = l {
+                assert!(ty.as_ref().map_or(true, |ty| Type::coerce_literal(ty) == Some(Type::Int)), "Literal inside type name has non-integer type. This should be done during analysis on the types instead.");
+                n.try_into().expect("Array length expression too large.")
+            } else {
+                panic!(
+                    "Array length expression not resolved in type name prior to conversion: {value}"
+                );
+            }
+        });
+        ArrayType {
+            base: Box::new(Type::from(*value.base)),
+            length,
+        }
+    }
+}
+
+impl<R> Children<Expression<R>> for ArrayType<Expression<R>> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        Box::new(self.base.children().chain(self.length.as_ref()))
+    }
+
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        Box::new(self.base.children_mut().chain(self.length.as_mut()))
+    }
+}
+
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub struct TupleType<E = u64> {
+    pub items: Vec<Type<E>>,
+}
+
+impl<E> TupleType<E> {
+    /// Unwraps single-element tuples and turns all other tuples into a tuple type.
+    /// If the components of `t` are normalized, `t.into_tuple().into_type()`
+    /// is equal to `t.normalize()`.
+    pub fn into_type(mut self) -> Type<E> {
+        if self.items.len() == 1 {
+            self.items.pop().unwrap()
+        } else {
+            Type::Tuple(self)
+        }
+    }
+}
+
+impl<R> Children<Expression<R>> for TupleType<Expression<R>> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        Box::new(self.items.iter().flat_map(|t| t.children()))
+    }
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        Box::new(self.items.iter_mut().flat_map(|t| t.children_mut()))
+    }
+}
+
+impl<R: Display> From<TupleType<Expression<R>>> for TupleType<u64> {
+    fn from(value: TupleType<Expression<R>>) -> Self {
+        TupleType {
+            items: value.items.into_iter().map(|t| t.into()).collect(),
+        }
+    }
+}
+
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub struct FunctionType<E = u64> {
+    pub params: Vec<Type<E>>,
+    pub value: Box<Type<E>>,
+}
+
+impl<R> Children<Expression<R>> for FunctionType<Expression<R>> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        Box::new(
+            self.params
+                .iter()
+                .flat_map(|t| t.children())
+                .chain(self.value.children()),
+        )
+    }
+
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        Box::new(
+            self.params
+                .iter_mut()
+                .flat_map(|t| t.children_mut())
+                .chain(self.value.children_mut()),
+        )
+    }
+}
+
+impl<R: Display> From<FunctionType<Expression<R>>> for FunctionType<u64> {
+    fn from(value: FunctionType<Expression<R>>) -> Self {
+        FunctionType {
+            params: value.params.into_iter().map(|t| t.into()).collect(),
+            value: Box::new((*value.value).into()),
+        }
+    }
+}
+
+impl From<FunctionType> for Type {
+    fn from(value: FunctionType) -> Self {
+        Type::Function(value)
+    }
+}
+
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub struct RecordType<E = u64> {
+    /// The names and types of the fields, in declaration order.
+    pub fields: Vec<(String, Type<E>)>,
+}
+
+impl<R> Children<Expression<R>> for RecordType<Expression<R>> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        Box::new(self.fields.iter().flat_map(|(_, t)| t.children()))
+    }
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        Box::new(self.fields.iter_mut().flat_map(|(_, t)| t.children_mut()))
+    }
+}
+
+impl<R: Display> From<RecordType<Expression<R>>> for RecordType<u64> {
+    fn from(value: RecordType<Expression<R>>) -> Self {
+        RecordType {
+            fields: value
+                .fields
+                .into_iter()
+                .map(|(n, t)| (n, t.into()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema, Hash,
+)]
+pub struct TypeScheme<E = u64> {
+    /// Type variables and their trait bounds.
+    pub vars: TypeBounds,
     /// The actual type (using the type variables from `vars` but potentially also other type variables)
     pub ty: Type<E>,
 }
@@ -466,10 +1944,228 @@ impl<E: Clone> TypeScheme<E> {
             ty,
         }
     }
+
+    /// Renames the quantified type variables (in `vars` and in `ty`) to the
+    /// names returned by `fresh`, which is called once per quantified type variable.
+    /// Type variables of `ty` that are not quantified are free and thus kept.
+    pub fn freshen_vars(&mut self, fresh: &mut impl FnMut(&str) -> String) {
+        let mapping: HashMap<String, String> =
+            self.vars.vars().map(|v| (v.clone(), fresh(v))).collect();
+        self.ty.rename_type_vars_with(&mapping);
+        self.vars = TypeBounds::new(
+            self.vars
+                .bounds()
+                .map(|(v, b)| (mapping[v].clone(), b.clone())),
+        );
+    }
+}
+impl<E> TypeScheme<E> {
+    pub fn type_vars_to_string(&self) -> String {
+        type_vars_to_string(&self.vars)
+    }
+
+    /// Returns the trait bounds of the type variable `var`, or None if
+    /// `var` is not quantified by this type scheme.
+    pub fn bounds_of(&self, var: &str) -> Option<&BTreeSet<String>> {
+        self.vars
+            .bounds()
+            .find_map(|(name, bounds)| (name == var).then_some(bounds))
+    }
+
+    /// Returns all distinct trait bounds of the quantified type variables,
+    /// i.e. the traits that are required by this type scheme.
+    pub fn all_bounds(&self) -> BTreeSet<String> {
+        self.vars
+            .bounds()
+            .flat_map(|(_, bounds)| bounds.iter().cloned())
+            .collect()
+    }
+
+    /// Returns true if any of the quantified type variables has a trait bound.
+    /// If not, the bounds do not need to be checked when instantiating the scheme.
+    pub fn has_nontrivial_bounds(&self) -> bool {
+        self.vars.bounds().any(|(_, bounds)| !bounds.is_empty())
+    }
+
+    /// Replaces all occurrences of `col` by `expr` in the type.
+    pub fn promote_cols(self) -> TypeScheme<E> {
+        TypeScheme {
+            vars: self.vars,
+            ty: self.ty.promote_cols(),
+        }
+    }
+}
+
+impl<E: PartialEq> TypeScheme<E> {
+    /// Returns true if the two type schemes are equal up to a renaming of
+    /// the quantified type variables, where the renamed type variables also
+    /// need to have the same trait bounds.
+    ///
+    /// This is the notion of equality required whenever the schemes are used
+    /// for type checking or instantiation.
+    pub fn alpha_eq(&self, other: &TypeScheme<E>) -> bool {
+        self.vars.len() == other.vars.len() && self.alpha_eq_impl(other, true)
+    }
+
+    /// Returns true if the types of the two type schemes are equal up to a
+    /// renaming of the quantified type variables, ignoring their trait bounds.
+    ///
+    /// Two schemes that are equal in this sense can still have different sets
+    /// of valid instantiations, so this must not be used in place of `alpha_eq`
+    /// during type checking. It is meant for cases where only the shape of the
+    /// type matters, like memoization of results that do not depend on the bounds.
+    pub fn ty_eq_ignoring_bounds(&self, other: &TypeScheme<E>) -> bool {
+        self.alpha_eq_impl(other, false)
+    }
+
+    fn alpha_eq_impl(&self, other: &TypeScheme<E>, compare_bounds: bool) -> bool {
+        let mut renaming = Default::default();
+        if !self
+            .ty
+            .eq_up_to_renaming(&other.ty, &mut renaming, &mut Default::default())
+        {
+            return false;
+        }
+        let bounds: HashMap<_, _> = self.vars.bounds().collect();
+        let other_bounds: HashMap<_, _> = other.vars.bounds().collect();
+        renaming.into_iter().all(|(var, other_var)| {
+            match (bounds.get(var), other_bounds.get(other_var)) {
+                (Some(b), Some(other_b)) => !compare_bounds || b == other_b,
+                // Type variables that are not quantified cannot be renamed.
+                (None, None) => var == other_var,
+                _ => false,
+            }
+        })
+    }
+}
+
+impl TypeScheme {
+    /// Builds a type scheme from `ty` that quantifies over all type variables
+    /// of `ty` that are not free in the environment, i.e. not in `env_vars`.
+    /// The quantified type variables are in the order of their first occurrence
+    /// and do not have any bounds.
+    pub fn generalize(ty: Type, env_vars: &HashSet<String>) -> TypeScheme {
+        let vars = ty
+            .contained_type_vars()
+            .filter(|v| !env_vars.contains(*v))
+            .map(|v| (v.clone(), BTreeSet::new()))
+            .collect::<Vec<_>>();
+        TypeScheme {
+            vars: TypeBounds::new(vars.into_iter()),
+            ty,
+        }
+    }
+}
+
+impl TypeScheme {
+    /// Checks that `substitution` can be used to instantiate this type scheme, i.e.
+    /// that it provides a type for each quantified type variable and that these types
+    /// satisfy the trait bounds of the type variables. Whether a type satisfies a trait bound
+    /// depends on the trait implementations, so it is decided by `satisfies_bound`.
+    /// Returns all problems found.
+    pub fn check_instantiation(
+        &self,
+        substitution: &HashMap<String, Type>,
+        satisfies_bound: &impl Fn(&Type, &str) -> bool,
+    ) -> Result<(), Vec<InstantiationError>> {
+        let errors = self
+            .vars
+            .bounds()
+            .flat_map(|(var, bounds)| match substitution.get(var) {
+                None => vec![InstantiationError::MissingTypeVar(var.clone())],
+                Some(ty) => bounds
+                    .iter()
+                    .filter(|bound| !satisfies_bound(ty, bound))
+                    .map(|bound| InstantiationError::UnsatisfiedBound {
+                        var: var.clone(),
+                        ty: ty.clone(),
+                        bound: bound.clone(),
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Instantiates each type scheme with its substitution after checking it
+    /// using `check_instantiation`. If any of the checks fails, returns all problems
+    /// found, together with the index of the type scheme.
+    pub fn instantiate_all(
+        schemes: &[(TypeScheme, HashMap<String, Type>)],
+        satisfies_bound: &impl Fn(&Type, &str) -> bool,
+    ) -> Result<Vec<Type>, Vec<(usize, InstantiationError)>> {
+        let errors = schemes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (scheme, substitution))| {
+                scheme
+                    .check_instantiation(substitution, satisfies_bound)
+                    .err()
+                    .map(|errors| errors.into_iter().map(move |e| (i, e)))
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(schemes
+            .iter()
+            .map(|(scheme, substitution)| {
+                let mut ty = scheme.ty.clone();
+                ty.substitute_type_vars(substitution);
+                ty
+            })
+            .collect())
+    }
+}
+
+/// The error returned by `Type::common_supertype`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoCommonType {
+    pub left: Type,
+    pub right: Type,
+}
+
+impl Display for NoCommonType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Types {} and {} do not have a common supertype",
+            self.left, self.right
+        )
+    }
 }
-impl<E> TypeScheme<E> {
-    pub fn type_vars_to_string(&self) -> String {
-        type_vars_to_string(&self.vars)
+
+/// A problem found by `TypeScheme::check_instantiation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstantiationError {
+    /// No type was given for the type variable.
+    MissingTypeVar(String),
+    /// The type given for the type variable does not satisfy one of its bounds.
+    UnsatisfiedBound {
+        var: String,
+        ty: Type,
+        bound: String,
+    },
+}
+
+impl Display for InstantiationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstantiationError::MissingTypeVar(var) => {
+                write!(f, "No type given for type variable {var}")
+            }
+            InstantiationError::UnsatisfiedBound { var, ty, bound } => {
+                write!(
+                    f,
+                    "Type {ty} given for {var} does not satisfy the bound {bound}"
+                )
+            }
+        }
     }
 }
 
@@ -525,3 +2221,1119 @@ impl TypeBounds {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn array(base: Type, length: Option<u64>) -> Type {
+        Type::Array(ArrayType {
+            base: Box::new(base),
+            length,
+        })
+    }
+
+    #[test]
+    fn forget_array_lengths() {
+        let mut t = array(array(Type::Int, Some(4)), Some(3));
+        t.forget_array_lengths();
+        assert_eq!(t, array(array(Type::Int, None), None));
+        assert_eq!(t.to_string(), "int[][]");
+    }
+
+    #[test]
+    fn set_array_length() {
+        let mut t = array(array(Type::Int, None), None);
+        t.set_array_length(3).unwrap();
+        assert_eq!(t, array(array(Type::Int, None), Some(3)));
+        // The inner array is not affected, it has to be set separately.
+        let Type::Array(ArrayType { base, .. }) = &mut t else {
+            unreachable!()
+        };
+        base.set_array_length(2).unwrap();
+        assert_eq!(t, array(array(Type::Int, Some(2)), Some(3)));
+
+        // Setting the same length again is fine, a different one is not.
+        t.set_array_length(3).unwrap();
+        assert_eq!(
+            t.set_array_length(4),
+            Err(
+                "Cannot set the length of array type int[2][3] to 4, it already has length 3."
+                    .to_string()
+            )
+        );
+        assert_eq!(t, array(array(Type::Int, Some(2)), Some(3)));
+
+        assert_eq!(
+            Type::<u64>::Int.set_array_length(3),
+            Err("Type int is not an array type.".to_string())
+        );
+        assert!(Type::<u64>::empty_tuple().set_array_length(0).is_err());
+        assert!(Type::<u64>::Optional(Box::new(array(Type::Int, None)))
+            .set_array_length(3)
+            .is_err());
+    }
+
+    #[test]
+    fn promote_cols() {
+        assert_eq!(
+            array(Type::Col, None).promote_cols(),
+            array(Type::Expr, None)
+        );
+        let fun = Type::Function(FunctionType {
+            params: vec![Type::Col, Type::Int],
+            value: Box::new(Type::Tuple(TupleType {
+                items: vec![Type::Col, Type::Fe],
+            })),
+        });
+        let expected = Type::Function(FunctionType {
+            params: vec![Type::Expr, Type::Int],
+            value: Box::new(Type::Tuple(TupleType {
+                items: vec![Type::Expr, Type::Fe],
+            })),
+        });
+        assert_eq!(fun.promote_cols(), expected);
+    }
+
+    #[test]
+    fn leaves() {
+        let t = Type::Tuple(TupleType {
+            items: vec![
+                Type::Int,
+                array(Type::Fe, None),
+                Type::TypeVar("T".to_string()),
+            ],
+        });
+        assert_eq!(t.leaves().collect::<Vec<_>>(), vec![&Type::Int, &Type::Fe]);
+    }
+
+    #[test]
+    fn coerce_literal() {
+        for ty in [
+            Type::Int,
+            Type::Fe,
+            Type::Expr,
+            Type::TypeVar("T".to_string()),
+        ] {
+            assert_eq!(Type::coerce_literal(&ty), Some(ty));
+        }
+        for ty in [
+            Type::Bool,
+            Type::String,
+            Type::Col,
+            Type::Inter,
+            Type::empty_tuple(),
+            array(Type::Int, Some(2)),
+            Type::Function(FunctionType {
+                params: vec![Type::Int],
+                value: Box::new(Type::Fe),
+            }),
+        ] {
+            assert_eq!(Type::coerce_literal(&ty), None, "{ty}");
+        }
+    }
+
+    #[test]
+    fn canonicalize() {
+        let fun = |a: &str, b: &str| {
+            Type::Function(FunctionType {
+                params: vec![
+                    Type::TypeVar(a.to_string()),
+                    Type::Tuple(TupleType {
+                        items: vec![array(Type::TypeVar(b.to_string()), None)],
+                    }),
+                ],
+                value: Box::new(Type::TypeVar(a.to_string())),
+            })
+        };
+        let t1 = fun("A", "B");
+        let t2 = fun("X", "T1");
+        assert_ne!(t1, t2);
+        assert_eq!(t1.clone().canonicalize().to_string(), "T1, T2[] -> T1");
+        assert_eq!(t1.clone().canonicalize(), t2.clone().canonicalize());
+
+        let keys: HashSet<CanonicalType> = [t1.into(), t2.into()].into_iter().collect();
+        assert_eq!(keys.len(), 1);
+    }
+
+    #[test]
+    fn same_shape() {
+        assert!(array(Type::Int, Some(4)).same_shape(&array(Type::Int, Some(8))));
+        assert!(array(Type::Int, Some(4)).same_shape(&array(Type::Int, None)));
+        assert!(!array(Type::Int, Some(4)).same_shape(&array(Type::Fe, Some(4))));
+        let pair = Type::Tuple(TupleType {
+            items: vec![Type::Int, Type::Int],
+        });
+        let single = Type::Tuple(TupleType {
+            items: vec![Type::Int],
+        });
+        assert!(!pair.same_shape(&single));
+    }
+
+    #[test]
+    fn into_tuple() {
+        let single = Type::Tuple(TupleType {
+            items: vec![array(Type::Int, None)],
+        });
+        let pair = Type::Tuple(TupleType {
+            items: vec![Type::Int, Type::Fe],
+        });
+        for t in [Type::Int, single, pair, Type::empty_tuple()] {
+            assert_eq!(t.clone().into_tuple().into_type(), t.normalize());
+        }
+        assert_eq!(
+            Type::<u64>::Fe.into_tuple(),
+            TupleType {
+                items: vec![Type::Fe]
+            }
+        );
+    }
+
+    #[test]
+    fn display_compact() {
+        let cases = [
+            // Parentheses around function types are needed in type terms.
+            ("((int -> fe))[]", "(int -> fe)[]"),
+            ("(int) -> ((fe -> int))", "int -> (fe -> int)"),
+            ("Option<(int -> fe)>", "Option<(int -> fe)>"),
+            ("{f: ((int -> fe))}?", "{f: (int -> fe)}?"),
+            // All other parentheses can be dropped.
+            ("((int, fe))[]", "(int, fe)[]"),
+            ("((int))[2]", "int[2]"),
+            ("((((int), fe[])))", "(int, fe[])"),
+            ("((int -> fe))", "int -> fe"),
+            ("Option<(Option<(int)>)>", "Option<Option<int> >"),
+        ];
+        for (source, expected) in cases {
+            let t = powdr_parser::parse_type(source).unwrap();
+            let compact = t.display_compact();
+            assert_eq!(compact, expected);
+            assert_eq!(powdr_parser::parse_type(&compact).unwrap(), t);
+        }
+        let single = Type::<u64>::Tuple(TupleType {
+            items: vec![Type::Int],
+        });
+        assert_eq!(single.display_compact(), "(int)");
+    }
+
+    #[test]
+    fn map_elementary() {
+        let t = array(Type::Fe, Some(3)).map_elementary(|t| match t {
+            Type::Fe => Type::Int,
+            t => t,
+        });
+        assert_eq!(t, array(Type::Int, Some(3)));
+    }
+
+    #[test]
+    fn is_valid_witness_type() {
+        let valid = Type::Tuple(TupleType {
+            items: vec![Type::Col, array(Type::Fe, Some(2))],
+        });
+        assert_eq!(valid.is_valid_witness_type(), Ok(()));
+        let err = array(array(Type::Col, None), Some(2))
+            .is_valid_witness_type()
+            .unwrap_err();
+        assert_eq!(err, WitnessTypeError::UnsizedArray("col[]".to_string()));
+        assert_eq!(
+            Type::<u64>::TypeVar("T".to_string())
+                .is_valid_witness_type()
+                .unwrap_err()
+                .to_string(),
+            "Type variable `T` has to be resolved to a concrete type to be the type of a witness column."
+        );
+    }
+
+    #[test]
+    fn to_rust_type_string() {
+        let t = Type::Tuple(TupleType {
+            items: vec![Type::Bool, array(Type::Fe, Some(4)), array(Type::Int, None)],
+        });
+        assert_eq!(
+            t.to_rust_type_string().unwrap(),
+            "(bool, [u64; 4], Vec<num_bigint::BigInt>)"
+        );
+        let single = Type::Tuple(TupleType {
+            items: vec![Type::Fe],
+        });
+        assert_eq!(single.to_rust_type_string().unwrap(), "(u64,)");
+        let fun = |param: Type| {
+            Type::Function(FunctionType {
+                params: vec![param],
+                value: Box::new(Type::Fe),
+            })
+        };
+        assert_eq!(
+            fun(Type::Int).to_rust_type_string().unwrap(),
+            "fn(num_bigint::BigInt) -> u64"
+        );
+        assert_eq!(fun(Type::Col).to_rust_type_string(), None);
+        assert_eq!(
+            Type::<u64>::TypeVar("T".to_string()).to_rust_type_string(),
+            None
+        );
+    }
+
+    #[test]
+    fn curry_uncurry() {
+        let fun = |param: Type, value: Type| {
+            Type::Function(FunctionType {
+                params: vec![param],
+                value: Box::new(value),
+            })
+        };
+        let curried = fun(Type::Int, fun(Type::Fe, Type::Bool));
+        assert_eq!(curried.to_string(), "int -> (fe -> bool)");
+        let uncurried = curried.uncurry();
+        assert_eq!(uncurried.to_string(), "int, fe -> bool");
+        assert_eq!(uncurried.curry(), curried);
+        assert_eq!(Type::<u64>::Int.uncurry(), Type::Int);
+    }
+
+    #[test]
+    fn display_truncated() {
+        let t = Type::Tuple(TupleType {
+            items: vec![Type::Int, array(array(Type::Fe, None), Some(2))],
+        });
+        assert_eq!(t.display_truncated(0), "(..., ...)");
+        assert_eq!(t.display_truncated(1), "(int, ...[2])");
+        assert_eq!(t.display_truncated(2), "(int, ...[][2])");
+        assert_eq!(t.display_truncated(3), t.to_string());
+        let fun = Type::<u64>::Function(FunctionType {
+            params: vec![array(Type::Int, None)],
+            value: Box::new(Type::Function(FunctionType {
+                params: vec![],
+                value: Box::new(Type::Fe),
+            })),
+        });
+        assert_eq!(fun.display_truncated(0), "... -> ...");
+        assert_eq!(fun.display_truncated(1), "...[] -> (-> ...)");
+        assert_eq!(fun.display_truncated(2), fun.to_string());
+    }
+
+    #[test]
+    fn is_fully_monomorphic() {
+        let unknown_length = array(Type::Int, None);
+        assert!(unknown_length.is_concrete_type());
+        assert!(!unknown_length.is_fully_monomorphic());
+        assert!(array(Type::Int, Some(2)).is_fully_monomorphic());
+        let with_var = array(Type::TypeVar("T".to_string()), Some(2));
+        assert!(!with_var.is_concrete_type());
+        assert!(!with_var.is_fully_monomorphic());
+    }
+
+    #[test]
+    fn split_function() {
+        let fun: Type = Type::Function(FunctionType {
+            params: vec![Type::Int, Type::Fe],
+            value: Box::new(Type::Bool),
+        });
+        assert_eq!(
+            fun.split_function(),
+            Ok((vec![Type::Int, Type::Fe], Type::Bool))
+        );
+        assert_eq!(Type::<u64>::Int.split_function(), Err(Type::Int));
+    }
+
+    #[test]
+    fn diff() {
+        let fun = |value: Type| {
+            Type::Tuple(TupleType {
+                items: vec![
+                    Type::Int,
+                    Type::Function(FunctionType {
+                        params: vec![Type::Int],
+                        value: Box::new(array(value, Some(2))),
+                    }),
+                ],
+            })
+        };
+        assert_eq!(fun(Type::Fe).diff(&fun(Type::Fe)), None);
+        let diff = fun(Type::Fe).diff(&fun(Type::Int)).unwrap();
+        assert_eq!(
+            diff.path,
+            TypePath(vec![
+                TypePathStep::TupleItem(1),
+                TypePathStep::ReturnType,
+                TypePathStep::ArrayBase
+            ])
+        );
+        assert_eq!(
+            diff.path.to_string(),
+            "tuple item 1 / return type / array element"
+        );
+        assert_eq!((diff.left, diff.right), (Type::Fe, Type::Int));
+
+        let diff = array(Type::Int, Some(2))
+            .diff(&array(Type::Int, Some(3)))
+            .unwrap();
+        assert_eq!(diff.path, TypePath::default());
+    }
+
+    #[test]
+    fn nested_array() {
+        let ty = Type::nested_array(Type::Int, &[2, 3, 4]);
+        assert_eq!(ty.to_string(), "int[2][3][4]");
+        assert_eq!(
+            ty.array_dims(),
+            (Type::Int, vec![Some(2), Some(3), Some(4)])
+        );
+        assert_eq!(array(Type::Fe, None).array_dims(), (Type::Fe, vec![None]));
+        assert_eq!(Type::Fe.array_dims(), (Type::Fe, vec![]));
+    }
+
+    #[test]
+    fn bounds_of() {
+        let scheme = TypeScheme {
+            vars: TypeBounds::new(
+                [
+                    ("T".to_string(), BTreeSet::from(["Add".to_string()])),
+                    ("U".to_string(), BTreeSet::new()),
+                ]
+                .into_iter(),
+            ),
+            ty: Type::TypeVar("T".to_string()),
+        };
+        assert_eq!(
+            scheme.bounds_of("T"),
+            Some(&BTreeSet::from(["Add".to_string()]))
+        );
+        assert_eq!(scheme.bounds_of("U"), Some(&BTreeSet::new()));
+        assert_eq!(scheme.bounds_of("V"), None);
+    }
+
+    #[test]
+    fn alpha_eq() {
+        let var = |name: &str| Type::<u64>::TypeVar(name.to_string());
+        let tuple = |items: Vec<Type>| Type::Tuple(TupleType { items });
+        let scheme = |vars: &[(&str, &[&str])], ty: Type| TypeScheme {
+            vars: TypeBounds::new(vars.iter().map(|(var, bounds)| {
+                (
+                    var.to_string(),
+                    bounds
+                        .iter()
+                        .map(|b| b.to_string())
+                        .collect::<BTreeSet<_>>(),
+                )
+            })),
+            ty,
+        };
+
+        // Consistent renaming, also of the bounds.
+        let t_u = scheme(
+            &[("T", &["Add"]), ("U", &[])],
+            tuple(vec![var("T"), array(var("U"), None), var("T")]),
+        );
+        let a_b = scheme(
+            &[("A", &["Add"]), ("B", &[])],
+            tuple(vec![var("A"), array(var("B"), None), var("A")]),
+        );
+        let u_t = scheme(
+            &[("U", &["Add"]), ("T", &[])],
+            tuple(vec![var("U"), array(var("T"), None), var("U")]),
+        );
+        for other in [&t_u, &a_b, &u_t] {
+            assert!(t_u.alpha_eq(other));
+            assert!(other.alpha_eq(&t_u));
+            assert!(t_u.ty_eq_ignoring_bounds(other));
+        }
+
+        // The renaming has to be injective, in both directions.
+        let pair = scheme(&[("T", &[]), ("U", &[])], tuple(vec![var("T"), var("U")]));
+        let same = scheme(&[("T", &[]), ("U", &[])], tuple(vec![var("T"), var("T")]));
+        assert!(!pair.alpha_eq(&same));
+        assert!(!same.alpha_eq(&pair));
+        assert!(!pair.ty_eq_ignoring_bounds(&same));
+        assert!(!same.ty_eq_ignoring_bounds(&pair));
+
+        // Types that only differ in the bounds.
+        let add = scheme(&[("T", &["Add"])], tuple(vec![var("T"), Type::Int]));
+        let mul = scheme(&[("S", &["Mul"])], tuple(vec![var("S"), Type::Int]));
+        let unbounded = scheme(&[("T", &[])], tuple(vec![var("T"), Type::Int]));
+        for (a, b) in [(&add, &mul), (&add, &unbounded), (&unbounded, &mul)] {
+            assert!(!a.alpha_eq(b));
+            assert!(!b.alpha_eq(a));
+            assert!(a.ty_eq_ignoring_bounds(b));
+            assert!(b.ty_eq_ignoring_bounds(a));
+        }
+    }
+
+    #[test]
+    fn matches_template() {
+        let var = |name: &str| Type::<u64>::TypeVar(name.to_string());
+        let tuple = |items: Vec<Type>| Type::Tuple(TupleType { items });
+        let pair = tuple(vec![var("T"), var("T")]);
+        assert!(tuple(vec![Type::Int, Type::Int]).matches_template(&pair));
+        assert!(!tuple(vec![Type::Int, Type::Fe]).matches_template(&pair));
+        assert!(tuple(vec![Type::Int, Type::Fe]).matches_template(&tuple(vec![var("T"), var("U")])));
+        assert!(
+            tuple(vec![Type::Int, Type::Int]).matches_template(&tuple(vec![var("T"), var("U")]))
+        );
+        let nested = tuple(vec![array(Type::Fe, Some(2)), array(Type::Fe, Some(2))]);
+        assert!(nested.matches_template(&pair));
+        assert!(nested.matches_template(&tuple(vec![var("T"), array(var("U"), Some(2))])));
+        assert!(!nested.matches_template(&tuple(vec![var("T"), array(var("U"), None)])));
+        assert!(Type::Int.matches_template(&var("T")));
+        // Type variables in the matched type are not wildcards.
+        assert!(!var("A").matches_template(&Type::Int));
+        assert!(var("A").matches_template(&var("T")));
+    }
+
+    #[test]
+    fn fold_with_path() {
+        let ty = Type::Function(FunctionType {
+            params: vec![Type::Col, array(Type::Col, None)],
+            value: Box::new(Type::Tuple(TupleType {
+                items: vec![Type::Col, Type::Int],
+            })),
+        });
+        // Only promote columns outside of function parameters.
+        let promoted = ty.fold_with_path(&mut |path, t: Type| match t {
+            Type::Col if !matches!(path.0.first(), Some(TypePathStep::Param(_))) => Type::Expr,
+            t => t,
+        });
+        assert_eq!(promoted.to_string(), "col, col[] -> (expr, int)");
+
+        let mut paths = vec![];
+        array(Type::Int, Some(2)).fold_with_path(&mut |path, t: Type| {
+            paths.push(path.to_string());
+            t
+        });
+        assert_eq!(paths, ["array element", "the type itself"]);
+    }
+
+    #[test]
+    fn generalize() {
+        let ty = Type::Function(FunctionType {
+            params: vec![Type::TypeVar("T".to_string())],
+            value: Box::new(Type::TypeVar("U".to_string())),
+        });
+        let scheme = TypeScheme::generalize(ty, &["U".to_string()].into());
+        assert_eq!(
+            format!("{} {}", scheme.type_vars_to_string(), scheme.ty),
+            "<T> T -> U"
+        );
+        assert_eq!(
+            TypeScheme::generalize(Type::Int, &Default::default()).vars,
+            TypeBounds::default()
+        );
+    }
+
+    #[test]
+    fn produces_constraints() {
+        let constr =
+            Type::<u64>::NamedType(SymbolPath::from_str("std::prelude::Constr").unwrap(), None);
+        assert!(constr.is_constraint());
+        assert!(constr.produces_constraints());
+        let fun = Type::Function(FunctionType {
+            params: vec![Type::Expr],
+            value: Box::new(array(constr.clone(), None)),
+        });
+        assert!(!fun.is_constraint());
+        assert!(fun.produces_constraints());
+        assert!(!Type::<u64>::Expr.produces_constraints());
+        let other = Type::<u64>::NamedType(
+            SymbolPath::from_str("std::utils::Option").unwrap(),
+            Some(vec![Type::Int]),
+        );
+        assert!(!other.produces_constraints());
+        let prefixed = Type::<u64>::NamedType(
+            SymbolPath::from_str("std::prelude::Constr::Constr").unwrap(),
+            None,
+        );
+        assert!(!prefixed.is_constraint());
+        let relative = Type::<u64>::NamedType(
+            SymbolPath::from_str("super::prelude::Constr").unwrap(),
+            None,
+        );
+        assert!(!relative.is_constraint());
+    }
+
+    #[test]
+    fn instantiate_all() {
+        let var = |name: &str| Type::TypeVar(name.to_string());
+        let scheme = TypeScheme {
+            vars: TypeBounds::new(
+                [
+                    ("T".to_string(), BTreeSet::from(["Add".to_string()])),
+                    ("U".to_string(), BTreeSet::new()),
+                ]
+                .into_iter(),
+            ),
+            ty: Type::Function(FunctionType {
+                params: vec![var("T")],
+                value: Box::new(var("U")),
+            }),
+        };
+        let substitution = |t: Type, u: Option<Type>| -> HashMap<String, Type> {
+            std::iter::once(("T".to_string(), t))
+                .chain(u.map(|u| ("U".to_string(), u)))
+                .collect()
+        };
+        let satisfies_bound = |ty: &Type, bound: &str| bound == "Add" && *ty != Type::Bool;
+
+        let types = TypeScheme::instantiate_all(
+            &[
+                (scheme.clone(), substitution(Type::Int, Some(Type::Bool))),
+                (scheme.clone(), substitution(Type::Fe, Some(Type::Fe))),
+            ],
+            &satisfies_bound,
+        )
+        .unwrap();
+        assert_eq!(types.iter().join(", "), "int -> bool, fe -> fe");
+
+        let errors = TypeScheme::instantiate_all(
+            &[
+                (scheme.clone(), substitution(Type::Int, Some(Type::Int))),
+                (scheme.clone(), substitution(Type::Bool, None)),
+            ],
+            &satisfies_bound,
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|(i, e)| format!("{i}: {e}"))
+                .collect::<Vec<_>>(),
+            [
+                "1: Type bool given for T does not satisfy the bound Add",
+                "1: No type given for type variable U"
+            ]
+        );
+    }
+
+    #[test]
+    fn as_column_array() {
+        assert_eq!(array(Type::Col, Some(4)).as_column_array(), Some(4));
+        assert_eq!(array(Type::Col, None).as_column_array(), None);
+        assert_eq!(array(Type::Expr, Some(4)).as_column_array(), None);
+        assert_eq!(Type::Col.as_column_array(), None);
+    }
+
+    #[test]
+    fn with_renamed_vars() {
+        let ty = Type::<u64>::Tuple(TupleType {
+            items: vec![
+                Type::TypeVar("A".to_string()),
+                array(Type::TypeVar("B".to_string()), None),
+                Type::TypeVar("A".to_string()),
+            ],
+        });
+        let mapping = [("A", "X"), ("B", "A")]
+            .into_iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect();
+        let renamed = ty.clone().with_renamed_vars(&mapping);
+        assert_eq!(renamed.to_string(), "(X, A[], X)");
+        let mut in_place = ty;
+        in_place.rename_type_vars_with(&mapping);
+        assert_eq!(in_place, renamed);
+    }
+
+    #[test]
+    fn as_expr_context() {
+        assert_eq!(Type::Fe.as_expr_context(), Type::Expr);
+        assert_eq!(Type::Col.as_expr_context(), Type::Expr);
+        assert_eq!(Type::Expr.as_expr_context(), Type::Expr);
+        assert_eq!(Type::Int.as_expr_context(), Type::Int);
+        assert_eq!(
+            array(Type::Inter, Some(3)).as_expr_context(),
+            array(Type::Expr, None)
+        );
+        assert_eq!(
+            array(Type::Fe, Some(3)).as_expr_context(),
+            array(Type::Fe, Some(3))
+        );
+    }
+
+    #[test]
+    fn optional() {
+        let ty = Type::Optional(Box::new(Type::<u64>::TypeVar("T".to_string())));
+        assert!(!ty.is_elementary());
+        assert!(!ty.needs_parentheses());
+        assert_eq!(ty.inner_optional(), Some(&Type::TypeVar("T".to_string())));
+        assert_eq!(Type::<u64>::Int.inner_optional(), None);
+        assert_eq!(ty.contained_type_vars().collect::<Vec<_>>(), vec!["T"]);
+        let mut substituted = ty.clone();
+        substituted.substitute_type_vars(&[("T".to_string(), Type::Int)].into_iter().collect());
+        assert_eq!(substituted, Type::Optional(Box::new(Type::Int)));
+        assert_eq!(substituted.to_string(), "int?");
+        let fun = Type::Optional(Box::new(Type::<u64>::Function(FunctionType {
+            params: vec![Type::Int],
+            value: Box::new(Type::Bool),
+        })));
+        assert_eq!(fun.to_string(), "(int -> bool)?");
+    }
+
+    #[test]
+    fn freshen_vars() {
+        let mut counter = 0;
+        let mut fresh = |name: &str| {
+            counter += 1;
+            format!("{name}_{counter}")
+        };
+        // The definition `T -> (T, S)` (with free type variable `S`) is inlined at a
+        // call site that already uses `T`.
+        let mut scheme = TypeScheme {
+            vars: TypeBounds::new(
+                [("T".to_string(), BTreeSet::from(["Add".to_string()]))].into_iter(),
+            ),
+            ty: Type::<u64>::Function(FunctionType {
+                params: vec![Type::TypeVar("T".to_string())],
+                value: Box::new(Type::Tuple(TupleType {
+                    items: vec![
+                        Type::TypeVar("T".to_string()),
+                        Type::TypeVar("S".to_string()),
+                    ],
+                })),
+            }),
+        };
+        scheme.freshen_vars(&mut fresh);
+        assert_eq!(
+            format!("{} {}", scheme.type_vars_to_string(), scheme.ty),
+            "<T_1: Add> T_1 -> (T_1, S)"
+        );
+        let call_site = Type::<u64>::Tuple(TupleType {
+            items: vec![Type::TypeVar("T".to_string()), scheme.ty.clone()],
+        });
+        assert_eq!(
+            call_site.contained_type_vars().collect::<Vec<_>>(),
+            vec!["T", "T_1", "S"]
+        );
+
+        let mut ty = call_site;
+        ty.freshen_vars(&mut fresh);
+        assert_eq!(ty.to_string(), "(T_2, T_1_3 -> (T_1_3, S_4))");
+    }
+
+    #[test]
+    fn is_representable_in_field() {
+        use powdr_number::GoldilocksField;
+        assert!(Type::Fe.is_representable_in_field::<GoldilocksField>());
+        assert!(array(Type::Expr, None).is_representable_in_field::<GoldilocksField>());
+        assert!(Type::Tuple(TupleType {
+            items: vec![Type::Bool, array(Type::Col, Some(2))]
+        })
+        .is_representable_in_field::<GoldilocksField>());
+        assert!(!Type::Int.is_representable_in_field::<GoldilocksField>());
+        assert!(!Type::String.is_representable_in_field::<GoldilocksField>());
+        assert!(!array(Type::Int, Some(3)).is_representable_in_field::<GoldilocksField>());
+    }
+
+    #[test]
+    fn field_layout() {
+        use FieldLayout::*;
+        assert_eq!(Type::Bool.field_layout(), Some(Element));
+        assert_eq!(
+            array(Type::Fe, Some(3)).field_layout(),
+            Some(Array {
+                element: Box::new(Element),
+                stride: 1,
+                length: 3,
+            })
+        );
+
+        let pair = Type::Tuple(TupleType {
+            items: vec![Type::Fe, array(Type::Expr, Some(2))],
+        });
+        let pair_layout = Sequence(vec![
+            (0, Element),
+            (
+                1,
+                Array {
+                    element: Box::new(Element),
+                    stride: 1,
+                    length: 2,
+                },
+            ),
+        ]);
+        assert_eq!(pair.field_layout(), Some(pair_layout.clone()));
+        assert_eq!(pair_layout.size(), 3);
+
+        // The stride of an array is the size of its elements.
+        let pairs = array(pair.clone(), Some(4));
+        assert_eq!(
+            pairs.field_layout(),
+            Some(Array {
+                element: Box::new(pair_layout.clone()),
+                stride: 3,
+                length: 4,
+            })
+        );
+        assert_eq!(pairs.field_layout().unwrap().size(), 12);
+
+        let record = Type::Record(RecordType {
+            fields: vec![
+                ("a".to_string(), pair),
+                ("b".to_string(), Type::Int),
+                ("c".to_string(), pairs),
+            ],
+        });
+        let record_layout = record.field_layout().unwrap();
+        assert_eq!(
+            record_layout,
+            Sequence(vec![
+                (0, pair_layout.clone()),
+                (3, Element),
+                (
+                    4,
+                    Array {
+                        element: Box::new(pair_layout),
+                        stride: 3,
+                        length: 4,
+                    }
+                ),
+            ])
+        );
+        assert_eq!(record_layout.size(), 16);
+        assert_eq!(Type::empty_tuple().field_layout(), Some(Sequence(vec![])));
+
+        assert_eq!(Type::Optional(Box::new(Type::Fe)).field_layout(), None);
+        assert_eq!(array(Type::Fe, None).field_layout(), None);
+        assert_eq!(array(array(Type::Fe, None), Some(2)).field_layout(), None);
+        assert_eq!(Type::String.field_layout(), None);
+        assert_eq!(
+            Type::Tuple(TupleType {
+                items: vec![Type::Fe, Type::String],
+            })
+            .field_layout(),
+            None
+        );
+    }
+
+    #[test]
+    fn count_nodes() {
+        let ty = Type::<u64>::Tuple(TupleType {
+            items: vec![Type::Int, array(Type::TypeVar("T".to_string()), None)],
+        });
+        assert_eq!(ty.count_nodes(), 4);
+        assert_eq!(Type::<u64>::Fe.count_nodes(), 1);
+        assert_eq!(Type::<u64>::empty_tuple().count_nodes(), 1);
+        let fun = Type::<u64>::Function(FunctionType {
+            params: vec![ty.clone(), Type::Bool],
+            value: Box::new(ty),
+        });
+        assert_eq!(fun.count_nodes(), 10);
+    }
+
+    #[test]
+    fn simplify_bottom() {
+        assert_eq!(Type::<u64>::Bottom.simplify_bottom(), Ok(Type::Bottom));
+        let diverging = Type::<u64>::Function(FunctionType {
+            params: vec![Type::Int],
+            value: Box::new(Type::Function(FunctionType {
+                params: vec![],
+                value: Box::new(Type::Bottom),
+            })),
+        });
+        assert_eq!(diverging.clone().simplify_bottom(), Ok(diverging));
+
+        let unresolved = Type::<u64>::Function(FunctionType {
+            params: vec![Type::Int],
+            value: Box::new(Type::Tuple(TupleType {
+                items: vec![Type::Int, array(Type::Bottom, None)],
+            })),
+        });
+        assert_eq!(
+            unresolved.simplify_bottom().unwrap_err().to_string(),
+            "return type / tuple item 1 / array element"
+        );
+        let param = Type::<u64>::Function(FunctionType {
+            params: vec![Type::Bottom],
+            value: Box::new(Type::Int),
+        });
+        assert_eq!(
+            param.simplify_bottom(),
+            Err(TypePath(vec![TypePathStep::Param(0)]))
+        );
+    }
+
+    #[test]
+    fn common_supertype() {
+        assert_eq!(Type::Int.common_supertype(&Type::Int), Ok(Type::Int));
+        assert_eq!(
+            Type::Bottom.common_supertype(&Type::String),
+            Ok(Type::String)
+        );
+        assert_eq!(Type::Fe.common_supertype(&Type::Col), Ok(Type::Expr));
+        assert_eq!(
+            array(Type::Col, Some(2)).common_supertype(&array(Type::Expr, Some(3))),
+            Ok(array(Type::Expr, None))
+        );
+        let t1 = Type::Tuple(TupleType {
+            items: vec![Type::Bottom, array(Type::Int, Some(2))],
+        });
+        let t2 = Type::Tuple(TupleType {
+            items: vec![Type::Bool, array(Type::Fe, Some(2))],
+        });
+        let err = t1.common_supertype(&t2).unwrap_err();
+        assert_eq!(
+            err,
+            NoCommonType {
+                left: Type::Int,
+                right: Type::Fe
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Types int and fe do not have a common supertype"
+        );
+        let t3 = Type::Tuple(TupleType {
+            items: vec![Type::Bool, array(Type::Int, None)],
+        });
+        assert_eq!(
+            t1.common_supertype(&t3).unwrap().to_string(),
+            "(bool, int[])"
+        );
+    }
+
+    #[test]
+    fn json_value() {
+        let ty = Type::Tuple(TupleType {
+            items: vec![Type::Bool, array(Type::TypeVar("T".to_string()), Some(2))],
+        });
+        let value = ty.to_json_value();
+        assert_eq!(
+            value,
+            serde_json::json!({"Tuple": {"items": [
+                "Bool",
+                {"Array": {"base": {"TypeVar": "T"}, "length": 2}}
+            ]}})
+        );
+        assert_eq!(Type::from_json_value(value.clone()).unwrap(), ty);
+
+        // The outermost variant of the value is one of the variants of the schema.
+        let schema = serde_json::to_value(schemars::schema_for!(Type)).unwrap();
+        let variants = schema["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|variant| {
+                let names = variant
+                    .get("enum")
+                    .or_else(|| variant.get("required"))
+                    .unwrap();
+                names.as_array().unwrap().clone()
+            })
+            .collect::<Vec<_>>();
+        assert!(variants.contains(&serde_json::json!("Bool")));
+        assert!(variants.contains(&serde_json::json!("Tuple")));
+        let (name, _) = value.as_object().unwrap().iter().next().unwrap();
+        assert!(variants.contains(&serde_json::json!(name)));
+    }
+
+    #[test]
+    fn elementary_name() {
+        for ty in [
+            Type::<u64>::Bottom,
+            Type::Bool,
+            Type::Int,
+            Type::Fe,
+            Type::String,
+            Type::Col,
+            Type::Inter,
+            Type::Expr,
+        ] {
+            assert!(ty.is_elementary());
+            assert_eq!(ty.elementary_name(), Some(ty.to_string().as_str()));
+        }
+        assert_eq!(Type::<u64>::Fe.elementary_name(), Some("fe"));
+        assert_eq!(Type::<u64>::Bottom.elementary_name(), Some("!"));
+        assert_eq!(array(Type::Fe, None).elementary_name(), None);
+        assert_eq!(
+            Type::<u64>::TypeVar("T".to_string()).elementary_name(),
+            None
+        );
+    }
+
+    #[test]
+    fn all_bounds() {
+        let scheme: TypeScheme = TypeScheme {
+            vars: TypeBounds::new(
+                [
+                    ("T", vec!["Add", "Mul"]),
+                    ("U", vec![]),
+                    ("V", vec!["Mul", "Ord"]),
+                ]
+                .into_iter()
+                .map(|(var, bounds)| {
+                    (
+                        var.to_string(),
+                        bounds
+                            .into_iter()
+                            .map(|b| b.to_string())
+                            .collect::<BTreeSet<_>>(),
+                    )
+                }),
+            ),
+            ty: Type::TypeVar("T".to_string()),
+        };
+        assert_eq!(
+            scheme.all_bounds().into_iter().collect::<Vec<_>>(),
+            vec!["Add", "Mul", "Ord"]
+        );
+        assert!(TypeScheme::from(Type::Int).all_bounds().is_empty());
+    }
+
+    #[test]
+    fn has_nontrivial_bounds() {
+        let scheme = |bounds: Vec<&str>| -> TypeScheme {
+            TypeScheme {
+                vars: TypeBounds::new(
+                    [
+                        ("T".to_string(), BTreeSet::new()),
+                        (
+                            "U".to_string(),
+                            bounds.into_iter().map(|b| b.to_string()).collect(),
+                        ),
+                    ]
+                    .into_iter(),
+                ),
+                ty: Type::TypeVar("T".to_string()),
+            }
+        };
+        assert!(!scheme(vec![]).has_nontrivial_bounds());
+        assert!(scheme(vec!["Ord"]).has_nontrivial_bounds());
+        assert!(!TypeScheme::from(Type::Int).has_nontrivial_bounds());
+    }
+
+    #[test]
+    fn map_array_lengths() {
+        let mut ty = Type::nested_array(Type::Int, &[2, 3]);
+        assert_eq!(ty.to_string(), "int[2][3]");
+        ty.map_array_lengths(|length| length.map(|l| l * 2));
+        assert_eq!(ty.to_string(), "int[4][6]");
+
+        let mut ty = Type::Tuple(TupleType {
+            items: vec![array(Type::Fe, None), array(Type::Int, Some(1))],
+        });
+        ty.map_array_lengths(|length| Some(length.unwrap_or(0) + 1));
+        assert_eq!(ty.to_string(), "(fe[1], int[2])");
+    }
+
+    #[test]
+    fn display_with_field() {
+        use powdr_number::{BabyBearField, GoldilocksField};
+        assert_eq!(
+            Type::Fe.display_with_field::<GoldilocksField>(),
+            "fe (Goldilocks)"
+        );
+        assert_eq!(
+            array(Type::Fe, Some(2)).display_with_field::<BabyBearField>(),
+            "fe[2] (BabyBear)"
+        );
+        let ty = Type::Function(FunctionType {
+            params: vec![Type::Int],
+            value: Box::new(Type::Fe),
+        });
+        assert_eq!(
+            ty.display_with_field::<GoldilocksField>(),
+            "int -> fe (Goldilocks)"
+        );
+        assert_eq!(
+            array(Type::Int, None).display_with_field::<GoldilocksField>(),
+            "int[]"
+        );
+        assert_eq!(Type::Expr.display_with_field::<GoldilocksField>(), "expr");
+    }
+
+    #[test]
+    fn unit() {
+        let unit = Type::<u64>::unit();
+        assert_eq!(unit.to_string(), "()");
+        assert!(unit.is_unit());
+        assert!(unit.normalize().is_unit());
+        assert!(!Type::<u64>::Int.is_unit());
+        let single = Type::<u64>::Tuple(TupleType {
+            items: vec![Type::unit()],
+        });
+        assert!(!single.is_unit());
+        assert!(single.normalize().is_unit());
+    }
+
+    #[test]
+    fn zip() {
+        let t1 = Type::Tuple(TupleType {
+            items: vec![
+                array(Type::Fe, Some(2)),
+                Type::Function(FunctionType {
+                    params: vec![Type::Int],
+                    value: Box::new(Type::TypeVar("T".to_string())),
+                }),
+            ],
+        });
+        let t2 = Type::Tuple(TupleType {
+            items: vec![
+                array(Type::Expr, None),
+                Type::Function(FunctionType {
+                    params: vec![Type::Int],
+                    value: Box::new(Type::Bool),
+                }),
+            ],
+        });
+        assert_eq!(
+            t1.zip(&t2).unwrap(),
+            vec![
+                (&Type::Fe, &Type::Expr),
+                (&Type::Int, &Type::Int),
+                (&Type::TypeVar("T".to_string()), &Type::Bool)
+            ]
+        );
+        assert!(Type::<u64>::unit().zip(&Type::unit()).unwrap().is_empty());
+
+        let t3 = Type::Tuple(TupleType {
+            items: vec![array(Type::Fe, Some(2)), Type::Int],
+        });
+        let mismatch = t1.zip(&t3).unwrap_err();
+        assert_eq!(mismatch.path, TypePath(vec![TypePathStep::TupleItem(1)]));
+        assert_eq!(mismatch.right, Type::Int);
+        assert_eq!(
+            Type::<u64>::unit().zip(&Type::Int).unwrap_err().path,
+            TypePath::default()
+        );
+    }
+
+    #[test]
+    fn num_type_vars() {
+        let var = |name: &str| Type::TypeVar(name.to_string());
+        let ty = Type::Tuple(TupleType {
+            items: vec![
+                var("T"),
+                Type::Function(FunctionType {
+                    params: vec![var("S"), array(var("T"), None)],
+                    value: Box::new(var("S")),
+                }),
+            ],
+        });
+        assert_eq!(ty.num_type_vars(), 2);
+        assert_eq!(ty.num_type_vars(), ty.contained_type_vars().count());
+        assert_eq!(Type::<u64>::Int.num_type_vars(), 0);
+    }
+
+    #[test]
+    fn check_against() {
+        let t: Type = Type::TypeVar("T".to_string());
+        let substitutions = array(t.clone(), None)
+            .check_against(&array(Type::Int, Some(2)))
+            .unwrap();
+        assert_eq!(substitutions, [("T".to_string(), Type::Int)].into());
+
+        let found = Type::Tuple(TupleType {
+            items: vec![t.clone(), array(Type::Fe, None)],
+        });
+        let expected = Type::Tuple(TupleType {
+            items: vec![Type::Int, array(t, None)],
+        });
+        let err = found.check_against(&expected).unwrap_err();
+        assert_eq!(
+            err,
+            TypeMismatch {
+                expected: Type::Int,
+                found: Type::Fe
+            }
+        );
+        assert_eq!(err.to_string(), "Expected `int`, found `fe`");
+    }
+}
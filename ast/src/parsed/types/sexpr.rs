@@ -0,0 +1,315 @@
+//! A compact s-expression encoding of types, meant for diffing types in tests:
+//! - elementary types are written as in the source language, except for the
+//!   bottom type, which is `bottom`,
+//! - `(array int 3)` and `(array int)` for arrays with and without length,
+//! - `(tuple int fe)` for tuples,
+//! - `(fn (int fe) bool)` for functions,
+//! - `(record (a int) (b fe))` for records,
+//! - `(optional int)` for optional types,
+//! - `(var T)` for type variables,
+//! - `(named std::utils::Option)` and `(named std::utils::Option (int))`
+//!   for named types without and with type arguments.
+
+use std::{fmt::Display, str::FromStr};
+
+use itertools::Itertools;
+
+use crate::parsed::asm::SymbolPath;
+
+use super::{ArrayType, FunctionType, RecordType, TupleType, Type};
+
+/// Formats `(head item1 item2 ...)`.
+fn list(head: &str, items: impl Iterator<Item = String>) -> String {
+    format!(
+        "({head}{})",
+        items.map(|item| format!(" {item}")).format("")
+    )
+}
+
+/// An error when decoding a type from its s-expression encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SexprError {
+    /// The input ended inside of an s-expression.
+    UnexpectedEnd,
+    /// A closing parenthesis without a matching opening parenthesis.
+    UnexpectedClosingParenthesis,
+    /// There is input after the first complete s-expression.
+    TrailingInput(String),
+    /// The s-expression is well-formed but does not encode a type.
+    InvalidType(String),
+}
+
+impl Display for SexprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SexprError::UnexpectedEnd => write!(f, "Unexpected end of input"),
+            SexprError::UnexpectedClosingParenthesis => {
+                write!(f, "Unexpected closing parenthesis")
+            }
+            SexprError::TrailingInput(input) => write!(f, "Unexpected input after type: {input}"),
+            SexprError::InvalidType(expr) => write!(f, "Not a valid type: {expr}"),
+        }
+    }
+}
+
+impl Type {
+    /// Encodes this type as an s-expression, see the module documentation for the format.
+    /// `Type::from_sexpr` decodes it again.
+    pub fn to_sexpr(&self) -> String {
+        let sexprs = |types: &[Type]| types.iter().map(|t| t.to_sexpr()).join(" ");
+        match self {
+            Type::Bottom => "bottom".to_string(),
+            Type::Bool
+            | Type::Int
+            | Type::Fe
+            | Type::String
+            | Type::Col
+            | Type::Inter
+            | Type::Expr => self.to_string(),
+            Type::Array(ArrayType { base, length }) => match length {
+                Some(length) => format!("(array {} {length})", base.to_sexpr()),
+                None => format!("(array {})", base.to_sexpr()),
+            },
+            Type::Tuple(TupleType { items }) => list("tuple", items.iter().map(|t| t.to_sexpr())),
+            Type::Function(FunctionType { params, value }) => {
+                format!("(fn ({}) {})", sexprs(params), value.to_sexpr())
+            }
+            Type::Record(RecordType { fields }) => list(
+                "record",
+                fields
+                    .iter()
+                    .map(|(name, ty)| format!("({name} {})", ty.to_sexpr())),
+            ),
+            Type::Optional(inner) => format!("(optional {})", inner.to_sexpr()),
+            Type::TypeVar(name) => format!("(var {name})"),
+            Type::NamedType(name, None) => format!("(named {name})"),
+            Type::NamedType(name, Some(args)) => format!("(named {name} ({}))", sexprs(args)),
+        }
+    }
+
+    /// Decodes a type from the s-expression produced by `Type::to_sexpr`.
+    pub fn from_sexpr(s: &str) -> Result<Type, SexprError> {
+        let mut tokens = tokenize(s).peekable();
+        let sexpr = SExpr::parse(&mut tokens)?;
+        let rest = tokens.join(" ");
+        if !rest.is_empty() {
+            return Err(SexprError::TrailingInput(rest));
+        }
+        sexpr.to_type()
+    }
+}
+
+fn tokenize(s: &str) -> impl Iterator<Item = &str> {
+    s.split_inclusive(['(', ')', ' ', '\t', '\n'])
+        .flat_map(|part| {
+            // Separate the delimiter at the end of the part from the atom before it.
+            let (atom, delimiter) =
+                part.split_at(part.len() - part.ends_with(['(', ')', ' ', '\t', '\n']) as usize);
+            [atom, delimiter]
+        })
+        .filter(|token| !token.trim().is_empty())
+}
+
+enum SExpr<'a> {
+    Atom(&'a str),
+    List(Vec<SExpr<'a>>),
+}
+
+impl<'a> SExpr<'a> {
+    fn parse(
+        tokens: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>,
+    ) -> Result<Self, SexprError> {
+        match tokens.next() {
+            None => Err(SexprError::UnexpectedEnd),
+            Some(")") => Err(SexprError::UnexpectedClosingParenthesis),
+            Some("(") => {
+                let mut items = vec![];
+                loop {
+                    match tokens.peek() {
+                        None => return Err(SexprError::UnexpectedEnd),
+                        Some(&")") => {
+                            tokens.next();
+                            return Ok(SExpr::List(items));
+                        }
+                        Some(_) => items.push(SExpr::parse(tokens)?),
+                    }
+                }
+            }
+            Some(atom) => Ok(SExpr::Atom(atom)),
+        }
+    }
+
+    fn to_type(&self) -> Result<Type, SexprError> {
+        let invalid = || SexprError::InvalidType(self.to_string());
+        let types = |items: &[SExpr]| -> Result<Vec<Type>, SexprError> {
+            items.iter().map(|t| t.to_type()).collect()
+        };
+        match self {
+            SExpr::Atom(atom) => match *atom {
+                "bottom" => Ok(Type::Bottom),
+                "bool" => Ok(Type::Bool),
+                "int" => Ok(Type::Int),
+                "fe" => Ok(Type::Fe),
+                "string" => Ok(Type::String),
+                "col" => Ok(Type::Col),
+                "inter" => Ok(Type::Inter),
+                "expr" => Ok(Type::Expr),
+                _ => Err(invalid()),
+            },
+            SExpr::List(items) => match items.as_slice() {
+                [SExpr::Atom("array"), base] => Ok(Type::Array(ArrayType {
+                    base: Box::new(base.to_type()?),
+                    length: None,
+                })),
+                [SExpr::Atom("array"), base, SExpr::Atom(length)] => Ok(Type::Array(ArrayType {
+                    base: Box::new(base.to_type()?),
+                    length: Some(length.parse().map_err(|_| invalid())?),
+                })),
+                [SExpr::Atom("tuple"), items @ ..] => Ok(Type::Tuple(TupleType {
+                    items: types(items)?,
+                })),
+                [SExpr::Atom("fn"), SExpr::List(params), value] => {
+                    Ok(Type::Function(FunctionType {
+                        params: types(params)?,
+                        value: Box::new(value.to_type()?),
+                    }))
+                }
+                [SExpr::Atom("record"), fields @ ..] => Ok(Type::Record(RecordType {
+                    fields: fields
+                        .iter()
+                        .map(|field| match field {
+                            SExpr::List(field) => match field.as_slice() {
+                                [SExpr::Atom(name), ty] => Ok((name.to_string(), ty.to_type()?)),
+                                _ => Err(invalid()),
+                            },
+                            SExpr::Atom(_) => Err(invalid()),
+                        })
+                        .collect::<Result<_, _>>()?,
+                })),
+                [SExpr::Atom("optional"), inner] => Ok(Type::Optional(Box::new(inner.to_type()?))),
+                [SExpr::Atom("var"), SExpr::Atom(name)] => Ok(Type::TypeVar(name.to_string())),
+                [SExpr::Atom("named"), SExpr::Atom(name)] => {
+                    Ok(Type::NamedType(SymbolPath::from_str(name).unwrap(), None))
+                }
+                [SExpr::Atom("named"), SExpr::Atom(name), SExpr::List(args)] => Ok(
+                    Type::NamedType(SymbolPath::from_str(name).unwrap(), Some(types(args)?)),
+                ),
+                _ => Err(invalid()),
+            },
+        }
+    }
+}
+
+impl Display for SExpr<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SExpr::Atom(atom) => write!(f, "{atom}"),
+            SExpr::List(items) => write!(f, "({})", items.iter().format(" ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Returns a variety of types built from the given types, nested one level deeper.
+    fn combine(types: &[Type]) -> Vec<Type> {
+        let mut result = vec![];
+        for (i, t) in types.iter().enumerate() {
+            let other = types[(i + 1) % types.len()].clone();
+            result.extend([
+                Type::Array(ArrayType {
+                    base: Box::new(t.clone()),
+                    length: (i % 2 == 0).then_some(i as u64),
+                }),
+                Type::Tuple(TupleType {
+                    items: vec![t.clone(), other.clone()],
+                }),
+                Type::Function(FunctionType {
+                    params: types[..i % 3].to_vec(),
+                    value: Box::new(t.clone()),
+                }),
+                Type::Record(RecordType {
+                    fields: vec![("a".to_string(), t.clone()), ("b".to_string(), other)],
+                }),
+                Type::Optional(Box::new(t.clone())),
+                Type::NamedType(
+                    SymbolPath::from_str("std::utils::Option").unwrap(),
+                    Some(vec![t.clone()]),
+                ),
+            ]);
+        }
+        result
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut types = vec![
+            Type::Bottom,
+            Type::Bool,
+            Type::Int,
+            Type::Fe,
+            Type::String,
+            Type::Col,
+            Type::Inter,
+            Type::Expr,
+            Type::TypeVar("T".to_string()),
+            Type::NamedType(SymbolPath::from_str("super::X").unwrap(), None),
+            Type::Tuple(TupleType { items: vec![] }),
+        ];
+        let level1 = combine(&types);
+        let level2 = combine(&level1);
+        types.extend(level1);
+        types.extend(level2);
+        for ty in types {
+            let sexpr = ty.to_sexpr();
+            assert_eq!(Type::from_sexpr(&sexpr), Ok(ty), "{sexpr}");
+        }
+    }
+
+    #[test]
+    fn format() {
+        let ty = Type::Function(FunctionType {
+            params: vec![Type::Int, Type::Fe],
+            value: Box::new(Type::Array(ArrayType {
+                base: Box::new(Type::Tuple(TupleType {
+                    items: vec![Type::Bool, Type::TypeVar("T".to_string())],
+                })),
+                length: Some(3),
+            })),
+        });
+        assert_eq!(
+            ty.to_sexpr(),
+            "(fn (int fe) (array (tuple bool (var T)) 3))"
+        );
+        assert_eq!(
+            Type::from_sexpr(" (fn\n(int  fe) (array (tuple bool (var T)) 3)) "),
+            Ok(ty)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Type::from_sexpr("(tuple int"),
+            Err(SexprError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Type::from_sexpr(")"),
+            Err(SexprError::UnexpectedClosingParenthesis)
+        );
+        assert_eq!(
+            Type::from_sexpr("int fe"),
+            Err(SexprError::TrailingInput("fe".to_string()))
+        );
+        assert_eq!(
+            Type::from_sexpr("(array int x)"),
+            Err(SexprError::InvalidType("(array int x)".to_string()))
+        );
+        assert_eq!(
+            Type::from_sexpr("(fn int bool)"),
+            Err(SexprError::InvalidType("(fn int bool)".to_string()))
+        );
+    }
+}
@@ -428,7 +428,12 @@ fn execute<F: FieldElement>(
     if continuations {
         let dry_run =
             powdr::riscv::continuations::rust_continuations_dry_run(&mut pipeline, profiling);
-        powdr::riscv::continuations::rust_continuations(&mut pipeline, generate_witness, dry_run)?;
+        powdr::riscv::continuations::rust_continuations(
+            &mut pipeline,
+            generate_witness,
+            dry_run,
+            None,
+        )?;
     } else {
         let fixed = pipeline.compute_fixed_cols().unwrap().clone();
         let asm = pipeline.compute_analyzed_asm().unwrap().clone();
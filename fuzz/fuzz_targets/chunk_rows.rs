@@ -0,0 +1,53 @@
+//! Drives the row accounting of the continuations dry run with synthetic traces.
+//!
+//! Run with `cargo fuzz run chunk_rows` from the `fuzz` directory.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use powdr_riscv::continuations::{chunk_rows, ChunkRows};
+
+fuzz_target!(|input: (u16, u16, u16, Vec<u16>)| {
+    let (full_trace_length, first_real_execution_row, num_rows, bootloader_rows) = input;
+    let full_trace_length = full_trace_length as usize;
+    // The rows before the first real execution row are part of the full trace.
+    let first_real_execution_row = first_real_execution_row as usize % (full_trace_length + 1);
+    let num_rows = num_rows as usize;
+    if bootloader_rows.is_empty() {
+        return;
+    }
+
+    let mut proven_trace = first_real_execution_row;
+    // Every chunk but the last one proves at least one new row.
+    let max_chunks = full_trace_length - first_real_execution_row + 1;
+    for (chunk_index, &start) in bootloader_rows.iter().cycle().enumerate() {
+        assert!(chunk_index < max_chunks, "the dry run does not terminate");
+        let start = start as usize;
+        // The chunk runs the bootloader and then continues where the previous chunk
+        // stopped, until the execution ends or the chunk is full.
+        let remaining_rows = full_trace_length - proven_trace;
+        let chunk_trace_len = (start + remaining_rows).min(num_rows);
+
+        let Some(ChunkRows {
+            new_rows,
+            is_last_chunk,
+        }) = chunk_rows(chunk_trace_len, start, num_rows)
+        else {
+            // Only a full chunk whose bootloader leaves no room for the computation
+            // is rejected.
+            assert_eq!(chunk_trace_len, num_rows);
+            assert!(start + 1 >= num_rows);
+            return;
+        };
+        assert!(is_last_chunk || new_rows > 0);
+        assert!(
+            new_rows <= remaining_rows,
+            "chunk proves rows beyond the trace"
+        );
+        proven_trace += new_rows;
+        if is_last_chunk {
+            break;
+        }
+    }
+    assert_eq!(proven_trace, full_trace_length);
+});
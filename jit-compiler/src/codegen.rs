@@ -6,7 +6,7 @@ use powdr_ast::{
     parsed::{
         asm::{Part, SymbolPath},
         display::quote,
-        types::{ArrayType, FunctionType, TupleType, Type, TypeScheme},
+        types::{ArrayType, FunctionType, RecordType, TupleType, Type, TypeScheme},
         visitor::AllChildren,
         ArrayLiteral, BinaryOperation, BinaryOperator, BlockExpression, EnumDeclaration,
         FunctionCall, IfExpression, IndexAccess, LambdaExpression, LetStatementInsideBlock,
@@ -632,6 +632,11 @@ fn map_type(ty: &Type) -> String {
         Type::Expr => "Expr".to_string(),
         Type::Array(ArrayType { base, length: _ }) => format!("PilVec<{}>", map_type(base)),
         Type::Tuple(TupleType { items }) => format!("({})", items.iter().map(map_type).join(", ")),
+        // Records are represented as tuples of their fields, in declaration order.
+        Type::Record(RecordType { fields }) => {
+            format!("({})", fields.iter().map(|(_, ty)| map_type(ty)).join(", "))
+        }
+        Type::Optional(inner) => format!("Option<{}>", map_type(inner)),
         Type::Function(ft) => format!(
             "Callable<({}), {}>",
             ft.params.iter().map(map_type).join(", "),
@@ -108,6 +108,20 @@ pub fn parse_type_scheme(vars: &str, ty: &str) -> TypeScheme {
     }
 }
 
+/// Constructs a `Type` from its source representation, mainly to simplify tests,
+/// e.g. `ty!(int -> fe[3])`. Type variables have to be declared in angle brackets
+/// in front of the type, as in `ty!(<T> T[] -> T)`, otherwise they are parsed as
+/// named types. Panics if the type cannot be parsed.
+#[macro_export]
+macro_rules! ty {
+    (<$($var:ident),*> $($t:tt)*) => {
+        $crate::parse_type_scheme(stringify!($($var),*), stringify!($($t)*)).ty
+    };
+    ($($t:tt)*) => {
+        $crate::parse_type_scheme("", stringify!($($t)*)).ty
+    };
+}
+
 /// Parse an escaped string - used in the grammar.
 pub fn unescape_string(s: &str) -> String {
     assert!(s.len() >= 2);
@@ -141,6 +155,29 @@ mod test {
     use test_log::test;
     use walkdir::WalkDir;
 
+    #[test]
+    fn ty_macro() {
+        use powdr_ast::parsed::types::{ArrayType, FunctionType, TupleType};
+        assert_eq!(
+            ty!(int -> fe[3]),
+            Type::Function(FunctionType {
+                params: vec![Type::Int],
+                value: Box::new(Type::Array(ArrayType {
+                    base: Box::new(Type::Fe),
+                    length: Some(3)
+                }))
+            })
+        );
+        assert_eq!(
+            ty!(<T> (T, bool)),
+            Type::Tuple(TupleType {
+                items: vec![Type::TypeVar("T".to_string()), Type::Bool]
+            })
+        );
+        assert_eq!(ty!(<A, B> A -> B).to_string(), "A -> B");
+        assert_eq!(ty!((int -> int)[]).to_string(), "(int -> int)[]");
+    }
+
     #[test]
     fn empty() {
         let input = "";
@@ -368,6 +405,29 @@ namespace Fibonacci(N);
         assert_eq!(input.trim(), printed.trim());
     }
 
+    #[test]
+    fn type_names_record() {
+        let input = r#"
+    let a: {};
+    let b: {x: int, y: fe[2]};
+    let c: {f: (int -> fe), g: {x: int}}[];
+    let d: int -> {x: int};"#;
+        let printed = format!("{}", parse(Some("input"), input).unwrap());
+        assert_eq!(input.trim(), printed.trim());
+    }
+
+    #[test]
+    fn type_names_optional() {
+        let input = r#"
+    let a: int?;
+    let b: int[]?[2];
+    let c: (int -> fe)?;
+    let d: int -> fe?;
+    let e: {x: int?}?;"#;
+        let printed = format!("{}", parse(Some("input"), input).unwrap());
+        assert_eq!(input.trim(), printed.trim());
+    }
+
     #[test]
     fn enum_decls() {
         let input = r#"
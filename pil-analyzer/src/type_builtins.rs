@@ -196,6 +196,8 @@ pub fn elementary_type_bounds(ty: &Type) -> &'static [&'static str] {
         Type::Array(t) if *t.base == Type::Expr => &["Add", "ToSelectedExprs"],
         Type::Array(_) => &["Add"],
         Type::Tuple(_) => &[],
+        Type::Record(_) => &[],
+        Type::Optional(_) => &[],
         Type::Function(_) => &[],
         Type::TypeVar(_) | Type::NamedType(_, _) => unreachable!(),
     }
@@ -997,12 +997,6 @@ fn update_type_if_literal(
         },
     ) = expr
     {
-        match expected_type.clone() {
-            Type::Int => *annotated_type = Some(Type::Int),
-            Type::Fe => *annotated_type = Some(Type::Fe),
-            Type::Expr => *annotated_type = Some(Type::Expr),
-            Type::TypeVar(tv) => *annotated_type = Some(Type::TypeVar(tv.clone())),
-            _ => {}
-        };
+        *annotated_type = Type::coerce_literal(expected_type);
     }
 }
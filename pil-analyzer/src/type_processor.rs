@@ -1,6 +1,6 @@
 use std::{collections::HashSet, str::FromStr};
 
-use powdr_ast::parsed::{asm::SymbolPath, types::Type, visitor::Children, Expression};
+use powdr_ast::parsed::{asm::SymbolPath, types::Type, visitor::Children, Expression, Number};
 use powdr_number::BigUint;
 
 use crate::{
@@ -58,7 +58,12 @@ impl<'a, D: AnalysisDriver> TypeProcessor<'a, D> {
             let v_u64: u64 = v.clone().try_into().map_err(|_| {
                 EvalError::TypeError(format!("Number too large, expected u64, but got {v}"))
             })?;
-            *e = BigUint::from(v_u64).into();
+            // Array lengths are integers.
+            *e = Number {
+                value: BigUint::from(v_u64),
+                type_: Type::coerce_literal(&Type::Int),
+            }
+            .into();
             Ok(())
         })?;
         Ok(t.into())
@@ -101,6 +101,22 @@ impl Unifier {
                     .zip(t2.items)
                     .try_for_each(|(i1, i2)| self.unify_types(i1, i2))
             }
+            (Type::Record(r1), Type::Record(r2)) => {
+                if r1.fields.len() != r2.fields.len()
+                    || r1
+                        .fields
+                        .iter()
+                        .zip(&r2.fields)
+                        .any(|((n1, _), (n2, _))| n1 != n2)
+                {
+                    return Err(format!("Record types have different fields: {r1} and {r2}"));
+                }
+                r1.fields
+                    .into_iter()
+                    .zip(r2.fields)
+                    .try_for_each(|((_, t1), (_, t2))| self.unify_types(t1, t2))
+            }
+            (Type::Optional(t1), Type::Optional(t2)) => self.unify_types(*t1, *t2),
             (Type::NamedType(n1, Some(args1)), Type::NamedType(n2, Some(args2))) if n1 == n2 => {
                 // The "None"-part is already handled with the equality comparison.
                 assert!(!args1.is_empty() && !args2.is_empty());
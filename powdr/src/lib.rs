@@ -330,7 +330,8 @@ pub fn prove(pipeline: &mut Pipeline<GoldilocksField>) {
         bootloader_inputs.bootloader_inputs.len()
     );
     let start = Instant::now();
-    riscv::continuations::rust_continuations(pipeline, generate_proof, bootloader_inputs).unwrap();
+    riscv::continuations::rust_continuations(pipeline, generate_proof, bootloader_inputs, None)
+        .unwrap();
     let duration = start.elapsed();
     log::info!("Proof generation for all chunks took: {:?}", duration);
 }
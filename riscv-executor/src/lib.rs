@@ -50,6 +50,10 @@ use crate::profiler::Profiler;
 /// TODO: get this value from some authoritative place
 const PC_INITIAL_VAL: usize = 2;
 
+/// Number of rows appended to the trace after the last executed row, which reset
+/// the PC and jump to the sink loop.
+pub const FINAL_SINK_ROWS: usize = 3;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Elem<F: FieldElement> {
     /// Only the ranges of i32 and u32 are actually valid for a Binary value.
@@ -239,6 +243,17 @@ pub struct MemOperation {
     pub row: usize,
     pub kind: MemOperationKind,
     pub address: u32,
+    /// The value read or written.
+    pub value: u32,
+}
+
+/// A write to the register memory.
+#[derive(Debug)]
+pub struct RegisterMemoryWrite<F: FieldElement> {
+    /// The row of the execution trace at which the write happened, like `MemOperation::row`.
+    pub row: usize,
+    pub address: u32,
+    pub value: F,
 }
 
 pub struct RegWrite<F: FieldElement> {
@@ -263,6 +278,9 @@ pub struct ExecutionTrace<F: FieldElement> {
     /// Writes and reads to memory.
     mem_ops: Vec<MemOperation>,
 
+    /// Writes to the register memory.
+    reg_mem_writes: Vec<RegisterMemoryWrite<F>>,
+
     /// The length of the trace, after applying the reg_writes.
     len: usize,
 
@@ -287,6 +305,7 @@ impl<F: FieldElement> ExecutionTrace<F> {
             reg_map,
             reg_writes,
             mem_ops: Vec::new(),
+            reg_mem_writes: Vec::new(),
             len: pc,
             cols,
         }
@@ -323,8 +342,82 @@ impl<F: FieldElement> ExecutionTrace<F> {
             .map(|(n, c)| (format!("main::{n}"), c))
             .collect()
     }
+
+    /// Same as `generate_registers_trace`, but builds the columns in parallel.
+    #[cfg(feature = "parallel")]
+    fn generate_registers_trace_parallel(&self) -> Vec<(String, Vec<Elem<F>>)> {
+        let chunk_size = self.len.div_ceil(rayon::current_num_threads());
+        // Splitting small traces does not pay off.
+        if chunk_size < 1 << 12 {
+            self.generate_registers_trace()
+        } else {
+            self.generate_registers_trace_in_chunks(chunk_size)
+        }
+    }
+
+    /// Builds the register columns by splitting the rows into chunks of `chunk_size` rows.
+    /// Replaying the register writes is inherently sequential, so the register values at
+    /// the start of each chunk are determined by a sequential replay first, which does not
+    /// copy any values. Then the partial columns of each chunk are built in parallel and
+    /// concatenated in order.
+    #[cfg(feature = "parallel")]
+    fn generate_registers_trace_in_chunks(&self, chunk_size: usize) -> Vec<(String, Vec<Elem<F>>)> {
+        use rayon::prelude::*;
+
+        let mut chunk_starts = vec![];
+        let mut rows = self.replay();
+        while rows.next_r < self.len {
+            chunk_starts.push(rows.clone());
+            for _ in 0..chunk_size {
+                rows.next_row();
+            }
+        }
+
+        let num_regs = self.reg_map.len();
+        let chunks = chunk_starts
+            .into_par_iter()
+            .map(|mut rows| {
+                let mut columns = (0..num_regs)
+                    .map(|_| Vec::with_capacity(chunk_size))
+                    .collect::<Vec<_>>();
+                for _ in 0..chunk_size {
+                    let Some(row) = rows.next_row() else {
+                        break;
+                    };
+                    for (column, value) in columns.iter_mut().zip(row) {
+                        column.push(Elem::Field(*value));
+                    }
+                }
+                columns
+            })
+            .collect::<Vec<_>>();
+
+        let mut parts = (0..num_regs)
+            .map(|_| Vec::with_capacity(chunks.len()))
+            .collect::<Vec<_>>();
+        for columns in chunks {
+            for (part, column) in parts.iter_mut().zip(columns) {
+                part.push(column);
+            }
+        }
+        let mut columns = parts
+            .into_par_iter()
+            .map(|part| part.into_iter().flatten().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        self.reg_map
+            .iter()
+            .map(|(reg_name, &index)| {
+                (
+                    format!("main::{reg_name}"),
+                    std::mem::take(&mut columns[index as usize]),
+                )
+            })
+            .collect()
+    }
 }
 
+#[derive(Clone)]
 pub struct TraceReplay<'a, F: FieldElement> {
     trace: &'a ExecutionTrace<F>,
     regs: Vec<F>,
@@ -391,7 +484,8 @@ mod builder {
     use crate::{
         BinaryMachine, Elem, ExecMode, Execution, ExecutionTrace, MemOperation, MemOperationKind,
         MemoryMachine, MemoryState, PoseidonGlMachine, PublicsMachine, RegWrite, RegisterMemory,
-        ShiftMachine, SplitGlMachine, Submachine, SubmachineBoxed, PC_INITIAL_VAL,
+        RegisterMemoryWrite, ShiftMachine, SplitGlMachine, Submachine, SubmachineBoxed,
+        PC_INITIAL_VAL,
     };
 
     fn namespace_degree_range<F: FieldElement>(
@@ -705,6 +799,7 @@ mod builder {
                     row: self.trace.len,
                     kind: MemOperationKind::Write,
                     address: addr,
+                    value: val,
                 });
                 self.memory_machine.write(step, addr, val.into(), selector);
             }
@@ -719,6 +814,7 @@ mod builder {
                     row: self.trace.len,
                     kind: MemOperationKind::Read,
                     address: addr,
+                    value: val,
                 });
                 self.memory_machine.read(step, addr, val.into(), selector);
             }
@@ -727,6 +823,13 @@ mod builder {
 
         pub(crate) fn set_reg_mem(&mut self, addr: u32, val: Elem<F>) {
             if addr != 0 {
+                if let ExecMode::Trace = self.mode {
+                    self.trace.reg_mem_writes.push(RegisterMemoryWrite {
+                        row: self.trace.len,
+                        address: addr,
+                        value: val.into_fe(),
+                    });
+                }
                 self.reg_mem.last.insert(addr, val);
             }
         }
@@ -756,6 +859,7 @@ mod builder {
                     memory_accesses: Vec::new(),
                     trace: HashMap::new(),
                     register_memory: HashMap::new(),
+                    register_memory_writes: Vec::new(),
                 };
             }
 
@@ -770,6 +874,9 @@ mod builder {
             };
 
             // turn register write operations into witness columns
+            #[cfg(feature = "parallel")]
+            let main_regs = self.trace.generate_registers_trace_parallel();
+            #[cfg(not(feature = "parallel"))]
             let main_regs = self.trace.generate_registers_trace();
             self.trace.cols.extend(main_regs);
 
@@ -836,6 +943,7 @@ mod builder {
                     })
                     .collect(),
                 register_memory: self.reg_mem.for_bootloader(),
+                register_memory_writes: self.trace.reg_mem_writes,
             }
         }
 
@@ -2329,6 +2437,8 @@ pub struct Execution<F: FieldElement> {
     pub memory_accesses: Vec<MemOperation>,
     /// final register memory state
     pub register_memory: RegisterMemoryState<F>,
+    /// sequence of register memory writes
+    pub register_memory_writes: Vec<RegisterMemoryWrite<F>>,
 }
 
 #[derive(Clone, Copy)]
@@ -2680,3 +2790,49 @@ pub fn write_executor_csv<F: FieldElement, P: AsRef<Path>>(
 fn is_multiple_of_4(n: u32) -> bool {
     n % 4 == 0
 }
+
+#[cfg(all(test, feature = "parallel"))]
+mod test {
+    use super::*;
+    use powdr_number::GoldilocksField;
+
+    #[test]
+    fn parallel_registers_trace() {
+        let reg_map: HashMap<String, u16> = [("pc", 0), ("x", 1), ("y", 2)]
+            .into_iter()
+            .map(|(n, i)| (n.to_string(), i))
+            .collect();
+        let reg_writes = (0..100)
+            .flat_map(|row| {
+                // Jump every 13 rows, so that the pc is not just incremented.
+                let jump = (row % 13 == 0).then(|| RegWrite {
+                    row,
+                    reg_idx: 0,
+                    val: GoldilocksField::from(row as u64 * 3),
+                });
+                let write = RegWrite {
+                    row,
+                    reg_idx: 1 + (row % 2) as u16,
+                    val: GoldilocksField::from(row as u64 * 7),
+                };
+                jump.into_iter().chain([write])
+            })
+            .collect();
+        let trace = ExecutionTrace::new(vec![], reg_map, reg_writes, 100);
+
+        let by_name = |columns: Vec<(String, Vec<Elem<GoldilocksField>>)>| {
+            columns
+                .into_iter()
+                .map(|(n, c)| (n, c.into_iter().map(|v| v.into_fe()).collect::<Vec<_>>()))
+                .collect::<HashMap<_, _>>()
+        };
+        let serial = by_name(trace.generate_registers_trace());
+        assert_eq!(by_name(trace.generate_registers_trace_parallel()), serial);
+        for chunk_size in [1, 7, 50, 99, 100, 1000] {
+            assert_eq!(
+                by_name(trace.generate_registers_trace_in_chunks(chunk_size)),
+                serial
+            );
+        }
+    }
+}
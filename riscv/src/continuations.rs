@@ -1,31 +1,50 @@
 use std::{
-    collections::BTreeSet,
-    fs::{create_dir_all, hard_link, remove_file},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{create_dir_all, hard_link, remove_file, File},
+    io::{BufReader, BufWriter, Write},
+    ops::Range,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
+use itertools::Itertools;
 use powdr_ast::{
-    asm_analysis::{AnalysisASMFile, Machine},
+    analyzed::Analyzed,
+    asm_analysis::{AnalysisASMFile, CallableSymbol, FunctionStatement, LabelStatement, Machine},
     parsed::{asm::parse_absolute_path, Expression, Number, PilStatement},
 };
-use powdr_number::{FieldElement, KnownField, LargeInt};
+use powdr_number::{DegreeType, FieldElement, KnownField, LargeInt, ReadWrite};
 use powdr_pipeline::Pipeline;
-use powdr_riscv_executor::{get_main_machine, MemoryState, ProfilerOptions};
+use powdr_riscv_executor::{
+    get_main_machine, Execution, FixedColumns, MemOperation, MemOperationKind, MemoryState,
+    ProfilerOptions, FINAL_SINK_ROWS,
+};
+use powdr_riscv_syscalls::Syscall;
+use powdr_schemas::SerializedAnalyzed;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 pub mod bootloader;
-mod memory_merkle_tree;
+pub mod memory_merkle_tree;
 
 use bootloader::split_fe;
 use bootloader::{
-    default_input, PAGE_SIZE_BYTES_LOG, PC_INDEX, REGISTER_MEMORY_NAMES, REGISTER_NAMES,
+    default_input, N_LEAVES_LOG, PAGE_SIZE_BYTES_LOG, PC_INDEX, REGISTER_MEMORY_NAMES,
+    REGISTER_NAMES,
 };
 use memory_merkle_tree::MerkleTree;
 
 use crate::continuations::bootloader::{
-    default_register_values, shutdown_routine_upper_bound, BOOTLOADER_INPUTS_PER_PAGE, DEFAULT_PC,
-    MEMORY_HASH_START_INDEX, PAGE_INPUTS_OFFSET, WORDS_PER_PAGE,
+    default_register_values, shutdown_routine_upper_bound, BOOTLOADER_INPUTS_PER_PAGE,
+    BYTES_PER_WORD, DEFAULT_PC, MEMORY_HASH_START_INDEX, NUM_PAGES_INDEX, PAGE_INPUTS_OFFSET,
+    WORDS_PER_PAGE,
 };
 
 use crate::code_gen::Register;
+use crate::runtime::syscall_handler_label;
 
 fn render_memory_hash<F: FieldElement>(hash: &[F]) -> String {
     // Main memory values must fit into u32
@@ -49,104 +68,431 @@ fn render_memory_hash<F: FieldElement>(hash: &[F]) -> String {
 ///    with all chunk-specific information set (witness, fixed cols, inputs, optimized pil)
 /// - `bootloader_inputs`: The inputs to the bootloader and the index of the row at which the shutdown routine
 ///   is supposed to execute, for each chunk, as returned by `rust_continuations_dry_run`.
+/// - `name_fn`: If set, called with the name of the pipeline and the index of a chunk to get
+///   the name of the directory of the chunk inside the output directory, instead of `chunk_{i}`.
+///   The pipeline needs to have a name in this case.
 pub fn rust_continuations<F: FieldElement, PipelineCallback, E>(
     pipeline: &mut Pipeline<F>,
     pipeline_callback: PipelineCallback,
     dry_run_result: DryRunResult<F>,
+    name_fn: Option<&dyn Fn(&str, usize) -> String>,
 ) -> Result<(), E>
 where
     PipelineCallback: Fn(&mut Pipeline<F>) -> Result<(), E>,
 {
+    run_chunks(pipeline, dry_run_result, name_fn, |_, pipeline| {
+        pipeline_callback(pipeline)
+    })
+}
+
+/// Runs `run_chunk` with the index and the prepared pipeline of each chunk,
+/// see `rust_continuations`.
+fn run_chunks<F: FieldElement, E>(
+    pipeline: &mut Pipeline<F>,
+    dry_run_result: DryRunResult<F>,
+    name_fn: Option<&dyn Fn(&str, usize) -> String>,
+    mut run_chunk: impl FnMut(usize, &mut Pipeline<F>) -> Result<(), E>,
+) -> Result<(), E> {
     let bootloader_inputs = dry_run_result.bootloader_inputs;
     let num_chunks = bootloader_inputs.len();
 
-    log::info!("Computing fixed columns...");
+    prepare_pipeline_for_chunks(pipeline);
+
+    bootloader_inputs.into_iter().enumerate().try_for_each(
+        |(i, (bootloader_inputs, start_of_shutdown_routine))| {
+            tracing::info!("\nRunning chunk {} / {}...", i + 1, num_chunks);
+            run_chunk_in_pipeline(
+                pipeline,
+                i,
+                bootloader_inputs,
+                start_of_shutdown_routine,
+                name_fn,
+                |pipeline| run_chunk(i, pipeline),
+            )
+        },
+    )
+}
+
+/// Sets the chunk-specific information of chunk `i` in the prepared `pipeline`
+/// and calls `run_chunk` with it. Afterwards, the output directory is restored.
+fn run_chunk_in_pipeline<F: FieldElement, E>(
+    pipeline: &mut Pipeline<F>,
+    i: usize,
+    bootloader_inputs: Vec<F>,
+    start_of_shutdown_routine: u64,
+    name_fn: Option<&dyn Fn(&str, usize) -> String>,
+    run_chunk: impl FnOnce(&mut Pipeline<F>) -> Result<(), E>,
+) -> Result<(), E> {
+    let parent_dir = pipeline.output_dir().clone();
+    let force_overwrite = pipeline.is_force_overwrite();
+
+    set_up_chunk(
+        pipeline,
+        i,
+        bootloader_inputs,
+        start_of_shutdown_routine,
+        name_fn,
+    );
+    let result = run_chunk(pipeline);
+
+    if let Some(original_dir) = parent_dir {
+        pipeline.set_output(original_dir, force_overwrite);
+    }
+
+    result
+}
+
+/// Sets the chunk-specific information of chunk `i` in the prepared `pipeline`:
+/// The output directory (if the pipeline has one) and the external witness values.
+fn set_up_chunk<F: FieldElement>(
+    pipeline: &mut Pipeline<F>,
+    i: usize,
+    bootloader_inputs: Vec<F>,
+    start_of_shutdown_routine: u64,
+    name_fn: Option<&dyn Fn(&str, usize) -> String>,
+) {
+    let parent_dir = pipeline.output_dir().clone();
+    let force_overwrite = pipeline.is_force_overwrite();
+
+    if let Some(parent_dir) = parent_dir {
+        let chunk_dir_name = match name_fn {
+            Some(name_fn) => name_fn(pipeline.name(), i),
+            None => format!("chunk_{i}"),
+        };
+        let chunk_dir = parent_dir.join(chunk_dir_name);
+        create_dir_all(&chunk_dir).unwrap();
+
+        // Hardlink constants.bin so that chunk dir will be self sufficient
+        let link_to_consts = chunk_dir.join("constants.bin");
+        if force_overwrite {
+            // Remove the file if it already exists
+            let _ = remove_file(&link_to_consts);
+        }
+        hard_link(parent_dir.join("constants.bin"), link_to_consts).unwrap();
+
+        // The output directory is set here to output witness and proof artifacts
+        // inside the chunk directory.
+        // TODO This is hacky and should be improved.
+        pipeline.set_output(chunk_dir, force_overwrite)
+    }
+
+    let length = main_length(pipeline);
+
+    pipeline.rollback_from_witness();
+
+    pipeline.add_external_witness_values_mut(vec![
+        bootloader_inputs_witness(bootloader_inputs),
+        jump_to_shutdown_routine_witness(length, start_of_shutdown_routine),
+    ]);
+}
+
+/// A pipeline prepared for running the chunks of an execution, see
+/// `prepare_pipeline_for_chunks`.
+///
+/// The bootloader inputs of each chunk computed by the dry run are self-contained:
+/// They include the register values and the memory root hash at the start and at the
+/// end of the chunk, as well as the accessed pages with their Merkle proofs. So chunks
+/// do not depend on each other and can be run in any order (e.g. by different workers)
+/// using `prove_single_chunk`.
+pub struct ContinuationSession<F: FieldElement> {
+    pipeline: Pipeline<F>,
+}
+
+impl<F: FieldElement> ContinuationSession<F> {
+    pub fn new(mut pipeline: Pipeline<F>) -> Self {
+        prepare_pipeline_for_chunks(&mut pipeline);
+        Self { pipeline }
+    }
+
+    pub fn pipeline(&self) -> &Pipeline<F> {
+        &self.pipeline
+    }
+
+    /// Writes the parts of the session that are expensive to compute, i.e. the optimized
+    /// PIL and the fixed columns, to `session.pilo` and `constants.bin` in the directory
+    /// `path`. See `load` for restoring the session.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        create_dir_all(path)
+            .map_err(|e| format!("Failed to create directory {}: {e}", path.display()))?;
+        let pil = self.pipeline.optimized_pil().map_err(|e| e.join("\n"))?;
+        SerializedAnalyzed::try_from(pil.as_ref())?.serialize_to(path.join(SESSION_PIL_FILE))?;
+        self.pipeline
+            .fixed_cols()
+            .map_err(|e| e.join("\n"))?
+            .write(&path.join("constants.bin"))
+            .map_err(|e| format!("Failed to write the fixed columns: {e}"))
+    }
+
+    /// Restores a session written by `save` from the directory `path`.
+    ///
+    /// Only the optimized PIL and the fixed columns are loaded. Everything else is taken
+    /// from the pipeline returned by `pipeline_factory`, which therefore has to be set up
+    /// like the pipeline of the saved session: the output directory, the backend and its
+    /// options and the prover inputs. If the pipeline has an output directory, the fixed
+    /// columns are written to it again, because the chunk directories link to them.
+    ///
+    /// The restored pipeline does not contain the assembly program, so it can only be
+    /// used to prove chunks, but not for a dry run. Persist the bootloader inputs of the
+    /// dry run separately, e.g. using `DryRunOptions::inputs_output_dir`.
+    pub fn load(
+        path: &Path,
+        pipeline_factory: impl FnOnce() -> Pipeline<F>,
+    ) -> Result<Self, String> {
+        let pipeline = pipeline_factory()
+            .from_pil_object(path.join(SESSION_PIL_FILE))
+            .map_err(|e| e.join("\n"))?
+            .read_constants(path)?;
+        if let Some(output_dir) = pipeline.output_dir() {
+            create_dir_all(output_dir)
+                .map_err(|e| format!("Failed to create directory {}: {e}", output_dir.display()))?;
+            pipeline
+                .fixed_cols()
+                .map_err(|e| e.join("\n"))?
+                .write(&output_dir.join("constants.bin"))
+                .map_err(|e| format!("Failed to write the fixed columns: {e}"))?;
+        }
+        Ok(Self { pipeline })
+    }
+}
+
+/// The name of the file in which `ContinuationSession::save` stores the optimized PIL.
+const SESSION_PIL_FILE: &str = "session.pilo";
+
+/// Calls `pipeline_callback` for chunk `chunk_index` with a pipeline prepared like
+/// in `rust_continuations`. `bootloader_inputs` are the inputs to the bootloader and
+/// the row of the shutdown routine of the chunk, i.e. the entry `chunk_index` of
+/// `DryRunResult::bootloader_inputs`.
+/// Chunks can be run in any order, see `ContinuationSession`.
+pub fn prove_single_chunk<F: FieldElement, E>(
+    session: &mut ContinuationSession<F>,
+    chunk_index: usize,
+    bootloader_inputs: (Vec<F>, u64),
+    pipeline_callback: impl FnOnce(&mut Pipeline<F>) -> Result<(), E>,
+) -> Result<(), E> {
+    tracing::info!("\nRunning chunk {chunk_index}...");
+    let (bootloader_inputs, start_of_shutdown_routine) = bootloader_inputs;
+    run_chunk_in_pipeline(
+        &mut session.pipeline,
+        chunk_index,
+        bootloader_inputs,
+        start_of_shutdown_routine,
+        None,
+        pipeline_callback,
+    )
+}
+
+/// Groups consecutive chunks such that the sum of the number of rows of the chunks
+/// of each group is at most `max_rows`, greedily adding chunks to the current group.
+/// A chunk with more than `max_rows` rows forms a group on its own.
+/// Returns the ranges of chunk indices of the groups, which cover all chunks in order.
+pub fn group_chunks(num_rows: &[u64], max_rows: u64) -> Vec<Range<usize>> {
+    let mut groups = vec![];
+    let mut start = 0;
+    let mut rows_in_group = 0;
+    for (i, &rows) in num_rows.iter().enumerate() {
+        if i > start && rows_in_group + rows > max_rows {
+            groups.push(start..i);
+            start = i;
+            rows_in_group = 0;
+        }
+        rows_in_group += rows;
+    }
+    if start < num_rows.len() {
+        groups.push(start..num_rows.len());
+    }
+    groups
+}
+
+/// Like `rust_continuations`, but groups consecutive chunks using `group_chunks`
+/// with the number of rows used by the chunks (see `ChunkStats::total_rows`) and
+/// `max_rows_per_batch`. `pipeline_callback` is called once per group with the index
+/// of the first chunk of the group and one prepared pipeline (as in `rust_continuations`)
+/// per chunk of the group, so that backends with a large fixed cost per proof can prove
+/// the chunks of a group together.
+///
+/// Grouping does not change the chunks: Each chunk still has its own bootloader inputs
+/// and witness and needs to be valid on its own, so a backend can only prove chunks
+/// together if it proves each of them (e.g. as separate instances of one proof),
+/// not their concatenation.
+///
+/// The pipelines of a group are clones of `pipeline`. The fixed columns and the optimized
+/// PIL are shared between them, but the other artifacts (e.g. the analyzed ASM and PIL)
+/// are copied, so the memory needed grows with the number of chunks in a group.
+pub fn rust_continuations_batched<F: FieldElement, PipelineCallback, E>(
+    pipeline: &mut Pipeline<F>,
+    pipeline_callback: PipelineCallback,
+    dry_run_result: DryRunResult<F>,
+    max_rows_per_batch: u64,
+) -> Result<(), E>
+where
+    PipelineCallback: Fn(usize, &mut [Pipeline<F>]) -> Result<(), E>,
+{
+    let bootloader_inputs = dry_run_result.bootloader_inputs;
+    assert_eq!(
+        dry_run_result.chunk_stats.len(),
+        bootloader_inputs.len(),
+        "the dry run result needs statistics for every chunk"
+    );
+    let num_rows = dry_run_result
+        .chunk_stats
+        .iter()
+        .map(|stats| stats.total_rows() as u64)
+        .collect::<Vec<_>>();
+    let groups = group_chunks(&num_rows, max_rows_per_batch);
+    tracing::info!(
+        "Running {} chunks in {} groups...",
+        bootloader_inputs.len(),
+        groups.len()
+    );
+
+    prepare_pipeline_for_chunks(pipeline);
+
+    let mut chunks = bootloader_inputs.into_iter().enumerate();
+    groups.into_iter().try_for_each(|group| {
+        tracing::info!("\nRunning chunks {group:?}...");
+        let first_chunk = group.start;
+        let mut pipelines = chunks
+            .by_ref()
+            .take(group.len())
+            .map(|(i, (bootloader_inputs, start_of_shutdown_routine))| {
+                let mut chunk_pipeline = pipeline.clone();
+                set_up_chunk(
+                    &mut chunk_pipeline,
+                    i,
+                    bootloader_inputs,
+                    start_of_shutdown_routine,
+                    None,
+                );
+                chunk_pipeline
+            })
+            .collect::<Vec<_>>();
+        pipeline_callback(first_chunk, &mut pipelines)
+    })
+}
+
+/// Returns the max degree of the `Main` machine, which determines the chunk size.
+/// Only the degree of `Main` is relevant, other machines can have different degrees.
+fn main_machine_max_degree<F: FieldElement>(
+    main_machine: &Machine,
+) -> Result<usize, ContinuationError<F>> {
+    match main_machine.degree.max.as_ref() {
+        Some(Expression::Number(_, n)) => n
+            .value
+            .clone()
+            .try_into()
+            .map_err(|_| ContinuationError::UnsupportedMainDegree(n.value.to_string())),
+        None => Err(ContinuationError::MissingMainDegree),
+        Some(e) => Err(ContinuationError::UnsupportedMainDegree(e.to_string())),
+    }
+}
+
+/// Like `rust_continuations`, but the pipeline of each chunk is created by
+/// `pipeline_factory`, which gets the index and the bootloader inputs of the chunk.
+/// This allows to customize the pipeline of each chunk, e.g. its output directory.
+///
+/// The factory is responsible for adding the bootloader inputs as external witness
+/// values (see `bootloader_inputs_witness`), the column that triggers the shutdown
+/// routine is added afterwards. To avoid computing the fixed columns and the optimized
+/// PIL for each chunk, the factory can clone a pipeline that was prepared once
+/// using `prepare_pipeline_for_chunks`.
+pub fn rust_continuations_with_pipelines<F: FieldElement, PipelineFactory, PipelineCallback, E>(
+    pipeline_factory: PipelineFactory,
+    pipeline_callback: PipelineCallback,
+    dry_run_result: DryRunResult<F>,
+) -> Result<(), E>
+where
+    PipelineFactory: Fn(usize, Vec<F>) -> Pipeline<F>,
+    PipelineCallback: Fn(&mut Pipeline<F>) -> Result<(), E>,
+{
+    let num_chunks = dry_run_result.bootloader_inputs.len();
+    for (i, (bootloader_inputs, start_of_shutdown_routine)) in
+        dry_run_result.bootloader_inputs.into_iter().enumerate()
+    {
+        tracing::info!("\nRunning chunk {} / {}...", i + 1, num_chunks);
+        let mut pipeline = pipeline_factory(i, bootloader_inputs);
+        pipeline.compute_optimized_pil().unwrap();
+        let length = main_length(&pipeline);
+        pipeline.add_external_witness_values_mut(vec![jump_to_shutdown_routine_witness(
+            length,
+            start_of_shutdown_routine,
+        )]);
+        pipeline_callback(&mut pipeline)?;
+    }
+    Ok(())
+}
+
+/// Computes the fixed columns and the optimized PIL, which are the same for all chunks,
+/// so that they don't need to be computed in every chunk.
+pub fn prepare_pipeline_for_chunks<F: FieldElement>(pipeline: &mut Pipeline<F>) {
+    tracing::info!("Computing fixed columns...");
     pipeline.compute_fixed_cols().unwrap();
 
     // Advance the pipeline to the optimized PIL stage, so that it doesn't need to be computed
     // in every chunk.
     pipeline.compute_optimized_pil().unwrap();
+}
 
-    bootloader_inputs
-        .into_iter()
-        .enumerate()
-        .map(
-            |(i, (bootloader_inputs, start_of_shutdown_routine))| -> Result<(), E> {
-                log::info!("\nRunning chunk {} / {}...", i + 1, num_chunks);
-
-                let parent_dir = pipeline.output_dir().clone();
-                let force_overwrite = pipeline.is_force_overwrite();
-
-                if let Some(parent_dir) = parent_dir.clone() {
-                    let chunk_dir = parent_dir.join(format!("chunk_{i}"));
-                    create_dir_all(&chunk_dir).unwrap();
-
-                    // Hardlink constants.bin so that chunk dir will be self sufficient
-                    let link_to_consts = chunk_dir.join("constants.bin");
-                    if force_overwrite {
-                        // Remove the file if it already exists
-                        let _ = remove_file(&link_to_consts);
-                    }
-                    hard_link(parent_dir.join("constants.bin"), link_to_consts).unwrap();
-
-                    // The output directory is set here to output witness and proof artifacts
-                    // inside the chunk directory.
-                    // TODO This is hacky and should be improved.
-                    pipeline.set_output(chunk_dir, force_overwrite)
-                }
+/// Returns the external witness values that pass the bootloader inputs of a chunk to the prover.
+pub fn bootloader_inputs_witness<F: FieldElement>(bootloader_inputs: Vec<F>) -> (String, Vec<F>) {
+    (
+        "main_bootloader_inputs::value".to_string(),
+        bootloader_inputs,
+    )
+}
 
-                // get the length of the main machine
-                // quite hacky, is there a better way?
-                let length = pipeline
-                    .optimized_pil()
-                    .unwrap()
-                    .definitions
-                    .iter()
-                    .find_map(|(name, (s, _))| match (name.starts_with("main::"), s) {
-                        (true, s) => s.degree.map(|d| d.max),
-                        _ => None,
-                    })
-                    .unwrap();
-
-                pipeline.rollback_from_witness();
-
-                // The `jump_to_shutdown_routine` column indicates when the execution should jump to the shutdown routine.
-                // In that row, the normal PC update is ignored and the PC is set to the address of the shutdown routine.
-                // In other words, it should be a one-hot encoding of `start_of_shutdown_routine`.
-                let jump_to_shutdown_routine = (0..length)
-                    .map(|i| (i == start_of_shutdown_routine - 1).into())
-                    .collect();
-                pipeline.add_external_witness_values_mut(vec![
-                    (
-                        "main_bootloader_inputs::value".to_string(),
-                        bootloader_inputs,
-                    ),
-                    (
-                        "main::jump_to_shutdown_routine".to_string(),
-                        jump_to_shutdown_routine,
-                    ),
-                ]);
-                pipeline_callback(pipeline)?;
-
-                if let Some(original_dir) = parent_dir {
-                    pipeline.set_output(original_dir, force_overwrite);
-                }
+/// Returns the `jump_to_shutdown_routine` column, which indicates when the execution should jump
+/// to the shutdown routine. In that row, the normal PC update is ignored and the PC is set to the
+/// address of the shutdown routine. In other words, it is a one-hot encoding of
+/// `start_of_shutdown_routine`.
+fn jump_to_shutdown_routine_witness<F: FieldElement>(
+    length: DegreeType,
+    start_of_shutdown_routine: u64,
+) -> (String, Vec<F>) {
+    (
+        "main::jump_to_shutdown_routine".to_string(),
+        (0..length)
+            .map(|i| (i == start_of_shutdown_routine - 1).into())
+            .collect(),
+    )
+}
 
-                Ok(())
-            },
-        )
-        .collect::<Result<Vec<_>, E>>()?;
-    Ok(())
+/// Returns the length of the main machine, requires the optimized PIL to be computed.
+fn main_length<F: FieldElement>(pipeline: &Pipeline<F>) -> DegreeType {
+    // quite hacky, is there a better way?
+    pipeline
+        .optimized_pil()
+        .unwrap()
+        .definitions
+        .iter()
+        .find_map(|(name, (s, _))| match (name.starts_with("main::"), s) {
+            (true, s) => s.degree.map(|d| d.max),
+            _ => None,
+        })
+        .unwrap()
 }
 
-fn sanity_check(main_machine: &Machine, field: KnownField) {
+fn sanity_check<F: FieldElement>(main_machine: &Machine, field: KnownField) {
+    // The bootloader inputs start with the memory registers, followed by the
+    // machine registers. Make sure `PC_INDEX` points to the PC in that layout.
+    assert_eq!(
+        REGISTER_NAMES.get(PC_INDEX.wrapping_sub(REGISTER_MEMORY_NAMES.len())),
+        Some(&"main::pc"),
+        "PC_INDEX ({PC_INDEX}) does not point to main::pc in the register layout of the bootloader inputs"
+    );
+    // The register values at the start of the first chunk need to have a value for each of them.
+    assert_eq!(
+        default_register_values::<F>().len(),
+        REGISTER_MEMORY_NAMES.len() + REGISTER_NAMES.len(),
+        "The default register values do not match the register layout of the bootloader inputs"
+    );
+
     for expected_instruction in bootloader::bootloader_specific_instruction_names(field) {
         if !main_machine
             .instructions
             .iter()
             .any(|i| i.name == expected_instruction)
         {
-            log::error!(
+            tracing::error!(
                 "Main machine is missing bootloader-specific instruction: {}. Did you set `with_bootloader` to true?",
                 expected_instruction
             );
@@ -155,13 +501,42 @@ fn sanity_check(main_machine: &Machine, field: KnownField) {
     }
 }
 
+/// Returns the pc of the `return` statement of the halt syscall handler, which is
+/// where a program that halts cleanly ends, or None if the handler is not found.
+///
+/// The pc of a statement is the index of its batch, offset such that the batch of
+/// the jump to the computation has the pc `DEFAULT_PC`, see there.
+fn default_halt_pc(main_machine: &Machine) -> Option<u64> {
+    let Some(CallableSymbol::Function(main_function)) = main_machine.callable.0.get("main") else {
+        return None;
+    };
+    let halt_label = syscall_handler_label(Syscall::Halt);
+    let batches = main_function
+        .body
+        .statements
+        .iter_batches()
+        .collect::<Vec<_>>();
+    let handler_index = batches.iter().position(|batch| {
+        batch.statements.iter().any(|s| {
+            matches!(s, FunctionStatement::Label(LabelStatement { name, .. }) if *name == halt_label)
+        })
+    })?;
+    let return_offset = batches[handler_index..].iter().position(|batch| {
+        batch
+            .statements
+            .iter()
+            .any(|s| matches!(s, FunctionStatement::Return(_)))
+    })?;
+    Some((handler_index + return_offset) as u64 + DEFAULT_PC - 1)
+}
+
 pub fn load_initial_memory(program: &AnalysisASMFile) -> MemoryState {
     let machine = get_main_machine(program);
     let Some(expr) = machine.pil.iter().find_map(|v| match v {
         PilStatement::LetStatement(_, n, _, expr) if n == "initial_memory" => expr.as_ref(),
         _ => None,
     }) else {
-        log::warn!("No initial_memory variable found in the machine. Assuming zeroed memory.");
+        tracing::warn!("No initial_memory variable found in the machine. Assuming zeroed memory.");
         return MemoryState::default();
     };
 
@@ -200,314 +575,2165 @@ pub struct DryRunResult<F: FieldElement> {
     pub bootloader_inputs: Vec<(Vec<F>, u64)>,
     // full execution trace length (i.e., length of main::pc)
     pub trace_len: usize,
+    pub summary: ContinuationSummary,
+    /// Number of executions per instruction, if requested through
+    /// `DryRunOptions::instruction_histogram_top_n`.
+    pub instruction_histogram: Option<HashMap<String, u64>>,
+    /// The root of the memory Merkle tree at the end of the execution.
+    pub final_memory_root: [F; 4],
+    /// The trace of the full execution, if requested through `DryRunOptions::keep_full_trace`.
+    /// See `register_state_at` to access the values at a certain row.
+    pub full_trace: Option<HashMap<String, Vec<F>>>,
+    /// Statistics of each chunk.
+    pub chunk_stats: Vec<ChunkStats>,
+    /// For each boundary between two chunks, the number of registers whose value at the
+    /// end of the later chunk is the same as at the end of the earlier chunk, if requested
+    /// through `DryRunOptions::count_unchanged_registers`.
+    pub unchanged_registers: Option<Vec<usize>>,
+    /// The digest of the bootloader inputs of each chunk, see `bootloader_inputs_digest`,
+    /// if requested through `DryRunOptions::compute_input_digests`.
+    pub input_digests: Option<Vec<[u8; 32]>>,
 }
 
-/// Runs the entire execution using the RISC-V executor. For each chunk, it collects:
-/// - The inputs to the bootloader, needed to restore the correct state.
-/// - The number of rows after which the prover should jump to the shutdown routine.
-pub fn rust_continuations_dry_run<F: FieldElement>(
-    pipeline: &mut Pipeline<F>,
-    profiler_opt: Option<ProfilerOptions>,
-) -> DryRunResult<F> {
-    let field = F::known_field().unwrap();
-
-    // All inputs for all chunks.
-    let mut bootloader_inputs_and_num_rows = vec![];
+/// Returns the SHA-256 digest of the canonical little-endian byte representations
+/// of the given bootloader inputs, which can be used to check that the inputs
+/// were not corrupted or as a key for caching the proofs of chunks.
+pub fn bootloader_inputs_digest<F: FieldElement>(bootloader_inputs: &[F]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for input in bootloader_inputs {
+        hasher.update(input.to_bytes_le());
+    }
+    hasher.finalize().into()
+}
 
-    // Initial register values for the current chunk.
-    let mut register_values = default_register_values();
+/// Statistics of a single chunk of the dry run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkStats {
+    pub chunk_index: usize,
+    /// The (sorted) indices of the pages paged in by the bootloader.
+    pub accessed_pages: Vec<u32>,
+    /// The number of rows of the chunk trace.
+    pub num_rows: usize,
+    /// The number of rows used by the bootloader.
+    pub bootloader_rows: usize,
+    /// The number of rows of the full trace that are newly proven by this chunk.
+    pub real_rows: usize,
+    /// The number of rows of the full trace that are proven by this and all previous chunks,
+    /// including the rows before the first real execution row.
+    pub cumulative_proven: usize,
+}
 
-    let asm = pipeline.compute_analyzed_asm().unwrap().clone();
-    let pil = pipeline.compute_optimized_pil().unwrap();
-    let fixed = pipeline.compute_fixed_cols().unwrap();
-    let main_machine = asm.get_machine(&parse_absolute_path("::Main")).unwrap();
-    sanity_check(main_machine, field);
+impl ChunkStats {
+    /// The header of the CSV format of `ChunkStats::to_csv_line`.
+    pub const CSV_HEADER: &'static str =
+        "chunk_index,accessed_pages,bootloader_rows,real_rows,cumulative_proven";
 
-    log::info!("Initializing memory merkle tree...");
+    /// Returns the statistics as a line of CSV (without line break),
+    /// using only the number of accessed pages.
+    pub fn to_csv_line(&self) -> String {
+        format!(
+            "{},{},{},{},{}",
+            self.chunk_index,
+            self.accessed_pages.len(),
+            self.bootloader_rows,
+            self.real_rows,
+            self.cumulative_proven
+        )
+    }
 
-    // Get initial memory contents from the special variable "initial_memory".
-    // In the first full run, we use it as the memory contents of the executor;
-    // on the independent chunk runs, the executor uses zeroed initial memory,
-    // and the pages are loaded via the bootloader.
-    let initial_memory = load_initial_memory(&asm);
+    /// The number of rows used by the chunk: The rows of the chunk trace (which include
+    /// the bootloader) and an upper bound of the rows of the shutdown routine.
+    pub fn total_rows(&self) -> usize {
+        self.num_rows + shutdown_routine_upper_bound(self.accessed_pages.len())
+    }
+}
 
-    let mut merkle_tree = MerkleTree::<F>::new();
-    merkle_tree.update(initial_memory.iter().map(|(k, v)| (*k, *v)));
+/// Writes a Graphviz DOT graph with a node for each chunk and an edge from
+/// each chunk to each page it accesses. Pages accessed by many chunks
+/// show up as hubs.
+pub fn write_continuation_dot(stats: &[ChunkStats], out: &mut impl Write) -> std::io::Result<()> {
+    writeln!(out, "digraph continuations {{")?;
+    writeln!(out, "  node [shape=box];")?;
+    for chunk in stats {
+        writeln!(
+            out,
+            "  chunk_{} [label=\"chunk {}\\n{} rows\"];",
+            chunk.chunk_index, chunk.chunk_index, chunk.real_rows
+        )?;
+    }
+    let pages = stats
+        .iter()
+        .flat_map(|chunk| &chunk.accessed_pages)
+        .collect::<BTreeSet<_>>();
+    for page in pages {
+        writeln!(out, "  page_{page} [shape=ellipse, label=\"page {page}\"];")?;
+    }
+    for chunk in stats {
+        for page in &chunk.accessed_pages {
+            writeln!(out, "  chunk_{} -> page_{page};", chunk.chunk_index)?;
+        }
+    }
+    writeln!(out, "}}")
+}
 
-    // TODO: commit to the merkle_tree root in the verifier.
+/// Returns the values of all columns of `full_trace` at the given row, keyed by
+/// column name. These are the witness columns of the main machine, i.e. the
+/// machine registers like `main::pc` or `main::X`, but also instruction flags and
+/// free values. The general-purpose registers of the RISC-V machine are stored
+/// in register memory and are not part of the trace.
+/// Returns None if the row is outside of the trace of any column.
+pub fn register_state_at<F: FieldElement>(
+    full_trace: &HashMap<String, Vec<F>>,
+    row: usize,
+) -> Option<HashMap<String, F>> {
+    full_trace
+        .iter()
+        .map(|(name, values)| Some((name.clone(), *values.get(row)?)))
+        .collect()
+}
 
-    log::info!("Initial execution...");
-    let full_exec = powdr_riscv_executor::execute::<F>(
-        &asm,
-        &pil,
-        fixed.clone(),
-        initial_memory,
-        pipeline.data_callback().unwrap(),
-        // Run full trace without any accessed pages. This would actually violate the
-        // constraints, but the executor does the right thing (read zero if the memory
-        // cell has never been accessed). We can't pass the accessed pages here, because
-        // we only know them after the full trace has been generated.
-        &default_input(&[]),
-        None,
-        profiler_opt,
+/// Panics if the two dry runs ended with different memory states.
+/// Comparing the results of two dry runs of the same program detects
+/// non-determinism in the memory handling of the executor.
+pub fn assert_same_memory_root<F: FieldElement>(a: &DryRunResult<F>, b: &DryRunResult<F>) {
+    let render = |root: &[F; 4]| {
+        render_memory_hash(&root.iter().flat_map(|e| split_fe(*e)).collect::<Vec<_>>())
+    };
+    assert_eq!(
+        a.final_memory_root,
+        b.final_memory_root,
+        "The final memory roots differ: {} != {}",
+        render(&a.final_memory_root),
+        render(&b.final_memory_root)
     );
+}
 
-    let full_trace_length = full_exec.trace_len;
-    log::info!("Total trace length: {}", full_trace_length);
+/// Aggregated statistics of a dry run, useful to judge how efficient the chunking was.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContinuationSummary {
+    pub num_chunks: usize,
+    /// Number of rows of the full trace that are covered by the chunks.
+    pub total_rows_proven: usize,
+    /// Number of rows spent in the bootloader, summed over all chunks.
+    pub total_bootloader_rows: usize,
+    pub avg_pages_per_chunk: f64,
+    pub max_pages_in_a_chunk: usize,
+}
 
-    let (first_real_execution_row, _) = full_exec.trace["main::pc"]
-        .iter()
-        .enumerate()
-        .find(|(_, &pc)| pc == DEFAULT_PC.into())
-        .unwrap();
+impl ContinuationSummary {
+    /// The number of bootloader rows per proven row of the full trace.
+    /// A high ratio indicates that the chunks are too small or access too many pages.
+    pub fn bootloader_overhead(&self) -> f64 {
+        self.total_bootloader_rows as f64 / self.total_rows_proven as f64
+    }
+}
 
-    // The number of rows of the full trace that we consider proven.
-    // Initialized with `first_real_execution_row`, because the bootloader
-    // execution in the first chunk will be different from the full trace
-    // execution (because of paged-in memory).
-    let mut proven_trace = first_real_execution_row;
-    let mut chunk_index = 0;
+impl std::fmt::Display for ContinuationSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} chunks, {} rows proven, {} bootloader rows ({:.2}% overhead), {:.2} pages per chunk on average (max {})",
+            self.num_chunks,
+            self.total_rows_proven,
+            self.total_bootloader_rows,
+            self.bootloader_overhead() * 100.0,
+            self.avg_pages_per_chunk,
+            self.max_pages_in_a_chunk
+        )
+    }
+}
 
-    let max_degree_expr = main_machine.degree.max.as_ref();
+#[derive(thiserror::Error, Debug)]
+pub enum ContinuationError<F: FieldElement> {
+    #[error("the dry run was cancelled after {} chunks", .bootloader_inputs.len())]
+    Cancelled {
+        /// The bootloader inputs of the chunks that were completed before the cancellation.
+        bootloader_inputs: Vec<(Vec<F>, u64)>,
+    },
+    #[error("the bootloader inputs of chunk {chunk_index} differ from the inputs to resume from")]
+    ResumeMismatch { chunk_index: usize },
+    #[error("the trace of chunk {chunk_index} differs from the full trace: {report}")]
+    TraceMismatch {
+        chunk_index: usize,
+        report: DivergenceReport,
+    },
+    #[error("{provided} chunks were provided to resume from, but the execution only has {actual} chunks")]
+    TooManyResumedChunks { provided: usize, actual: usize },
+    #[error("the {trace} never reaches pc = {pc}, observed pcs: {}", .observed_pcs.iter().join(", "))]
+    PcNotReached {
+        /// Which trace was searched, e.g. "full trace" or "trace of chunk 3".
+        trace: String,
+        pc: F,
+        /// All distinct values of the pc in the trace.
+        observed_pcs: BTreeSet<F>,
+    },
+    #[error("continuations rely on `Main` defining a max degree, but it does not define one")]
+    MissingMainDegree,
+    #[error("continuations rely on `Main` defining its max degree as a number, found {0}")]
+    UnsupportedMainDegree(String),
+    #[error("invalid memory accesses: {0}")]
+    InvalidMemoryAccesses(String),
+    #[error("failed to write the bootloader inputs of chunk {chunk_index}: {error}")]
+    WriteInputs {
+        chunk_index: usize,
+        error: std::io::Error,
+    },
+    #[error("failed to write the chunk statistics: {0}")]
+    WriteStats(std::io::Error),
+    #[error("failed to write the register values at the chunk boundaries: {0}")]
+    WriteTransitionRegisters(std::io::Error),
+    #[error(
+        "the chunks prove {proven_rows} rows, but the full trace has {full_trace_length} rows"
+    )]
+    CoverageMismatch {
+        /// The rows before the first real execution row plus the new rows of all chunks.
+        proven_rows: usize,
+        full_trace_length: usize,
+    },
+    #[error("the execution ended at pc {final_pc} instead of the halt pc {expected}, the program did not halt")]
+    UnexpectedFinalPc { expected: u64, final_pc: u64 },
+    #[error("the trace of the last chunk only has {trace_len} rows, which is too short to end in the sink loop")]
+    TraceTooShort { trace_len: usize },
+    #[error("chunk {chunk_index} has {chunk_trace_len} rows when run for at most {num_rows} rows, with {bootloader_rows} bootloader rows, so it does not prove any new rows")]
+    NoProgress {
+        chunk_index: usize,
+        chunk_trace_len: usize,
+        bootloader_rows: usize,
+        num_rows: usize,
+    },
+    #[error("the program did not halt within {steps} steps")]
+    NonTerminating { steps: usize },
+    #[error("page {page} {origin} is outside of the memory, which only has {num_pages} pages")]
+    PageOutOfRange {
+        page: u64,
+        origin: PageOrigin,
+        num_pages: u64,
+    },
+}
 
-    let length: usize = match max_degree_expr {
-        Some(Expression::Number(_, n)) => n.value.clone().try_into().unwrap(),
-        None => unimplemented!("Continuations rely on `Main` defining a max degree"),
-        Some(e) => {
-            unimplemented!("Continuations rely on `Main` not using a complex expression as its max degree, found {e}")
-        }
-    };
+/// Why a page is paged in by a chunk, see `ContinuationError::PageOutOfRange`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageOrigin {
+    /// The page is accessed by the program.
+    Access { row: usize, address: u64 },
+    /// The page is in `DryRunOptions::always_resident_pages`.
+    AlwaysResident,
+}
 
-    loop {
-        log::info!("\nRunning chunk {} for {} steps...", chunk_index, length);
-
-        log::info!("Building bootloader inputs for chunk {}...", chunk_index);
-        let mut accessed_pages = BTreeSet::new();
-        let mut accessed_addresses = BTreeSet::new();
-
-        let start_idx = full_exec
-            .memory_accesses
-            .binary_search_by_key(&proven_trace, |a| a.row)
-            .unwrap_or_else(|v| v);
-
-        for access in &full_exec.memory_accesses[start_idx..] {
-            // proven_trace + length is an upper bound for the last row index we'll reach in the next chunk.
-            // In practice, we'll stop earlier, because the bootloader & shutdown routine need to run as well,
-            // but we don't know for how long as that depends on the number of pages.
-            if access.row >= proven_trace + length {
-                break;
+impl std::fmt::Display for PageOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PageOrigin::Access { row, address } => {
+                write!(f, "(accessed in row {row} at address {address:#x})")
             }
-            accessed_addresses.insert(access.address);
-            accessed_pages.insert(access.address >> PAGE_SIZE_BYTES_LOG);
-        }
-        log::info!(
-            "{} unique memory accesses over {} accessed pages: {:?}",
-            accessed_addresses.len(),
-            accessed_pages.len(),
-            accessed_pages
-        );
+            PageOrigin::AlwaysResident => write!(f, "(always resident)"),
+        }
+    }
+}
 
-        let shutdown_routine_rows = shutdown_routine_upper_bound(accessed_pages.len());
-        log::info!(
-            "Estimating the shutdown routine to use {} rows.",
-            shutdown_routine_rows
-        );
-        let num_rows = length - shutdown_routine_rows;
-
-        // Build the bootloader inputs for the current chunk.
-        // Note that while we do know the accessed pages, we don't yet know the hashes
-        // of those pages at the end of the execution, because that will depend on how
-        // long the bootloader runs.
-        // Similarly, we don't yet know the final register values.
-        // So, we do a bit of a hack: For now, we'll just pretend that the state does not change, i.e.:
-        // - The final register values are equal to the initial register values.
-        // - The updated page hashes are equal to the current page hashes.
-        // - The updated root hash is equal to the current root hash.
-        // After simulating the chunk execution, we'll replace those values with the actual values.
-        let mut bootloader_inputs = bootloader::create_input(
-            register_values,
-            &merkle_tree,
-            accessed_pages.iter().cloned(),
-        );
+/// Returns the index of the page in the memory Merkle tree, or an error if the
+/// page is outside of the memory covered by the tree.
+fn page_index<F: FieldElement>(page: u64, origin: PageOrigin) -> Result<u32, ContinuationError<F>> {
+    let num_pages = 1u64 << N_LEAVES_LOG;
+    if page < num_pages {
+        Ok(page as u32)
+    } else {
+        Err(ContinuationError::PageOutOfRange {
+            page,
+            origin,
+            num_pages,
+        })
+    }
+}
 
-        log::info!("Bootloader inputs length: {}", bootloader_inputs.len());
-        log::info!(
-            "Initial memory root hash: {}",
-            render_memory_hash(
-                &bootloader_inputs[MEMORY_HASH_START_INDEX..MEMORY_HASH_START_INDEX + 8]
-            )
-        );
+/// Options to customize the dry run, see `rust_continuations_dry_run_with_options`.
+#[derive(Default)]
+pub struct DryRunOptions<'a, F: FieldElement> {
+    /// Bootloader inputs of the first chunks, as produced by a previous (partial) dry run.
+    /// The given chunks are not executed again: Only their bootloaders are run to find
+    /// the rows of the full trace they prove, and the memory and register writes of these
+    /// rows are applied to the state. The inputs are checked to be equal to the ones a
+    /// fresh run produces.
+    pub resume_from: Vec<Vec<F>>,
+    /// If set, the dry run is cancelled as soon as the flag is set. The flag is checked
+    /// before each chunk.
+    pub cancel: Option<&'a AtomicBool>,
+    /// If set, computes how often each instruction is executed in the full trace
+    /// and logs the given number of most executed instructions.
+    pub instruction_histogram_top_n: Option<usize>,
+    /// The pc at which the actual computation starts after the bootloader,
+    /// defaults to `DEFAULT_PC`. Only needs to be set for custom bootloaders.
+    pub default_pc: Option<u64>,
+    /// If set, the bootloader inputs of each chunk are written to `chunk_{i}.inputs`
+    /// in this directory as soon as they are computed, see `write_chunk_inputs`.
+    pub inputs_output_dir: Option<&'a Path>,
+    /// If set, the bootloader inputs are not kept in memory and the returned
+    /// inputs are empty, only the number of rows of each chunk is returned.
+    /// Only useful together with `inputs_output_dir`.
+    pub discard_bootloader_inputs: bool,
+    /// If set, these memory accesses are used to determine the pages accessed by
+    /// each chunk instead of the ones recorded during the full trace execution,
+    /// e.g. because they were computed by a prior analysis. They need to be sorted
+    /// by row, all rows need to be inside the full trace and all addresses need to
+    /// fit into 32 bits.
+    pub memory_accesses: Option<Vec<ContinuationMemoryAccess>>,
+    /// Sorted rows of the full trace at which chunks should preferably end, e.g. loop
+    /// boundaries. If one of them is at most `boundary_slack` rows before the regular
+    /// end of a chunk, the chunk is executed again and ends at this row instead, so that
+    /// the next chunk does not need to page in the working set of the loop again.
+    pub prefer_boundaries: Option<&'a [usize]>,
+    /// See `prefer_boundaries`.
+    pub boundary_slack: usize,
+    /// How register values are formatted when a chunk trace differs from the full trace.
+    pub value_format: ValueFormat,
+    /// If set, the trace of the full execution is returned as part of the result.
+    pub keep_full_trace: bool,
+    /// Pages that are paged in by the first chunk, whether it accesses them or not, and
+    /// then kept in memory across chunks: Later chunks start with their current values
+    /// and neither page them in nor out, which saves the bootloader and shutdown rows
+    /// of these pages. Their writes are still applied to the Merkle tree, so the final
+    /// memory root does not change. All of them need to be inside the memory covered
+    /// by the Merkle tree.
+    ///
+    /// Note that the current bootloader does not support this: Each chunk starts with
+    /// zeroed memory and only the pages in its inputs are loaded and checked against
+    /// the Merkle tree. For the inputs of later chunks to be provable, the PIL would
+    /// have to carry the memory of these pages over from one chunk to the next, i.e.
+    /// the memory machine of a chunk would have to start with the final values of
+    /// these pages in the previous chunk, and the last chunk would have to page them
+    /// out. Until then, this option can only be used to measure the savings.
+    pub always_resident_pages: BTreeSet<u64>,
+    /// If set, used instead of equality to compare the chunk traces with the full trace.
+    /// Called with the register name, the offset of the row relative to the start of
+    /// the comparison, the value in the chunk trace and the value in the full trace.
+    /// Returning false is treated as a difference.
+    pub compare: Option<&'a dyn Fn(&str, usize, F, F) -> bool>,
+    /// If set and a chunk trace differs from the full trace, the rows of both traces
+    /// from a few rows before the start of the comparison up to a few rows after the
+    /// first difference are written to `chunk_{i}_chunk.trace` and `chunk_{i}_full.trace`
+    /// in this directory, in a format suitable for diff tools.
+    pub trace_dump_dir: Option<&'a Path>,
+    /// If set, a CSV header and then one line of statistics per chunk are written here
+    /// as soon as the chunk is done, see `ChunkStats::CSV_HEADER`.
+    pub stats_csv: Option<&'a mut dyn Write>,
+    /// How thoroughly the chunk traces are compared with the full trace.
+    pub validation_level: ValidationLevel,
+    /// If set, the number of registers that do not change across each chunk boundary
+    /// is returned in `DryRunResult::unchanged_registers`.
+    pub count_unchanged_registers: bool,
+    /// If set, the digest of the bootloader inputs of each chunk is returned in
+    /// `DryRunResult::input_digests`, also if the inputs are discarded.
+    pub compute_input_digests: bool,
+    /// The pc the last chunk is expected to end at, which shows that the program halted.
+    /// Defaults to the pc of the `return` statement of the main function.
+    pub expected_halt_pc: Option<u64>,
+    /// If set, the register values at the end of each chunk, i.e. the values the next
+    /// chunk starts with, are written to this file, see `write_transition_registers`.
+    pub transition_registers_json: Option<&'a Path>,
+    /// If set, the full trace is run for at most this many steps, i.e. rows of the trace
+    /// without the `FINAL_SINK_ROWS` rows appended after the execution. If the program
+    /// does not halt within them, the dry run fails with `ContinuationError::NonTerminating`.
+    pub max_steps: Option<usize>,
+}
 
-        log::info!("Simulating chunk execution...");
-        let chunk_exec = powdr_riscv_executor::execute::<F>(
-            &asm,
-            &pil,
-            fixed.clone(),
-            MemoryState::new(),
-            pipeline.data_callback().unwrap(),
-            &bootloader_inputs,
-            Some(num_rows),
-            // profiling was done when full trace was generated
-            None,
-        );
+/// How thoroughly the dry run compares the traces of the chunks with the full trace.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValidationLevel {
+    /// The traces are not compared.
+    None,
+    /// Only the first row of the actual computation of each chunk is compared,
+    /// which checks that the chunk starts in the right state.
+    ChunkBoundaries,
+    /// All rows of the actual computation of each chunk are compared.
+    #[default]
+    Full,
+}
 
-        let mut memory_updates_by_page =
-            merkle_tree.organize_updates_by_page(chunk_exec.memory.into_iter());
-        for (i, &page_index) in accessed_pages.iter().enumerate() {
-            let page_index = page_index as usize;
-            let (_, _, proof) = merkle_tree.get(page_index);
-
-            // Replace the proof
-            let proof_start_index =
-                PAGE_INPUTS_OFFSET + BOOTLOADER_INPUTS_PER_PAGE * i + 1 + WORDS_PER_PAGE + 8;
-            for (j, sibling) in proof.into_iter().enumerate() {
-                bootloader_inputs[proof_start_index + j * 8..proof_start_index + j * 8 + 8]
-                    .copy_from_slice(
-                        &sibling
-                            .iter()
-                            .flat_map(|e| split_fe(*e))
-                            .collect::<Vec<_>>(),
-                    );
-            }
+/// The rows of the full trace proven by a chunk, see `chunk_rows`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkRows {
+    /// The number of rows of the full trace the chunk proves in addition to the
+    /// rows proven by the previous chunks.
+    pub new_rows: usize,
+    /// Whether the execution ends in this chunk.
+    pub is_last_chunk: bool,
+}
 
-            // Update one child of the Merkle tree
-            merkle_tree.update_page(
-                page_index,
-                &memory_updates_by_page
-                    .remove(&page_index)
-                    .unwrap_or_default(),
-            );
+/// Computes the rows of the full trace proven by a chunk that was run for at most
+/// `num_rows` rows and has a trace of length `chunk_trace_len`, the first
+/// `bootloader_rows` of which are used by the bootloader.
+/// Returns `None` if the row counts are inconsistent or a chunk that is not the
+/// last one does not prove any new rows, in which case the dry run would not terminate.
+pub fn chunk_rows(
+    chunk_trace_len: usize,
+    bootloader_rows: usize,
+    num_rows: usize,
+) -> Option<ChunkRows> {
+    match chunk_trace_len.cmp(&num_rows) {
+        std::cmp::Ordering::Less => Some(ChunkRows {
+            new_rows: chunk_trace_len.checked_sub(bootloader_rows)?,
+            is_last_chunk: true,
+        }),
+        std::cmp::Ordering::Equal => Some(ChunkRows {
+            // Minus one, because the last row will have to be repeated in the next chunk.
+            new_rows: num_rows
+                .checked_sub(bootloader_rows + 1)
+                .filter(|&rows| rows > 0)?,
+            is_last_chunk: false,
+        }),
+        std::cmp::Ordering::Greater => None,
+    }
+}
 
-            let (_, page_hash, proof) = merkle_tree.get(page_index);
+/// Returns the number of rows to execute in a chunk such that the next chunk starts at
+/// the last of the (sorted) `boundaries` that is at most `slack` rows before the regular
+/// start of the next chunk, if there is such a boundary.
+/// `start` is the number of rows used by the bootloader of the chunk.
+fn rows_until_preferred_boundary(
+    boundaries: &[usize],
+    slack: usize,
+    proven_trace: usize,
+    start: usize,
+    num_rows: usize,
+) -> Option<usize> {
+    // Row of the full trace at which the next chunk would start, as computed below
+    // from the number of new rows.
+    let next_start = proven_trace + num_rows - start - 1;
+    let boundary = *boundaries[..boundaries.partition_point(|&b| b < next_start)].last()?;
+    (boundary > proven_trace && boundary + slack >= next_start)
+        .then(|| num_rows - (next_start - boundary))
+}
 
-            // Assert the proof hasn't changed (because we didn't update any page except the current).
-            for (j, sibling) in proof.into_iter().enumerate() {
-                assert_eq!(
-                    &bootloader_inputs[proof_start_index + j * 8..proof_start_index + j * 8 + 8],
-                    sibling
-                        .iter()
-                        .flat_map(|e| split_fe(*e))
-                        .collect::<Vec<_>>()
-                );
-            }
+/// A memory access of the program, as used by the dry run to determine the pages
+/// accessed by each chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContinuationMemoryAccess {
+    /// The row of the full trace in which the access happens.
+    pub row: usize,
+    pub address: u64,
+    pub is_write: bool,
+}
 
-            // Replace the page hash
-            let updated_page_hash_index =
-                PAGE_INPUTS_OFFSET + BOOTLOADER_INPUTS_PER_PAGE * i + 1 + WORDS_PER_PAGE;
-            bootloader_inputs[updated_page_hash_index..updated_page_hash_index + 8]
-                .copy_from_slice(
-                    &page_hash
-                        .iter()
-                        .flat_map(|e| split_fe(*e))
-                        .collect::<Vec<_>>(),
-                );
+impl ContinuationMemoryAccess {
+    /// The index of the page the accessed address belongs to.
+    pub fn page(&self) -> u64 {
+        self.address >> PAGE_SIZE_BYTES_LOG
+    }
+}
+
+impl From<&MemOperation> for ContinuationMemoryAccess {
+    fn from(op: &MemOperation) -> Self {
+        Self {
+            row: op.row,
+            address: op.address as u64,
+            is_write: matches!(op.kind, MemOperationKind::Write),
         }
+    }
+}
 
-        // Go over all memory registers
-        register_values = REGISTER_MEMORY_NAMES
-            .iter()
-            .map(|reg| {
-                let reg = reg.strip_prefix("main::").unwrap();
-                let id = Register::from(reg).addr();
-                *chunk_exec
-                    .register_memory
-                    .get(&(id as u32))
-                    .unwrap_or(&0.into())
-            })
-            .collect::<Vec<_>>();
+/// Checks that the memory accesses are sorted by row, fit into a trace of length `trace_len`
+/// and only access 32-bit addresses.
+fn validate_memory_accesses(
+    accesses: &[ContinuationMemoryAccess],
+    trace_len: usize,
+) -> Result<(), String> {
+    if let Some(access) = accesses
+        .iter()
+        .find(|access| u32::try_from(access.address).is_err())
+    {
+        return Err(format!(
+            "access at row {} to address {} is outside of the 32-bit address space",
+            access.row, access.address
+        ));
+    }
+    if let Some((i, _)) = accesses
+        .iter()
+        .tuple_windows()
+        .find_position(|(a, b)| a.row > b.row)
+    {
+        return Err(format!(
+            "accesses are not sorted by row: access {} is at row {}, but access {} is at row {}",
+            i,
+            accesses[i].row,
+            i + 1,
+            accesses[i + 1].row
+        ));
+    }
+    match accesses.last() {
+        Some(last) if last.row >= trace_len => Err(format!(
+            "access at row {} is outside of the trace of length {trace_len}",
+            last.row
+        )),
+        _ => Ok(()),
+    }
+}
 
-        // Go over all machine registers
-        for reg in REGISTER_NAMES {
-            register_values.push(*chunk_exec.trace[reg].last().unwrap());
-        }
+/// The version of the format of the files written by `write_chunk_inputs`.
+/// Needs to be increased whenever the format or the layout of the bootloader
+/// inputs changes.
+pub const CHUNK_INPUTS_FORMAT_VERSION: u32 = 1;
 
-        // Replace final register values of the current chunk
-        bootloader_inputs[(REGISTER_MEMORY_NAMES.len() + REGISTER_NAMES.len())
-            ..2 * (REGISTER_MEMORY_NAMES.len() + REGISTER_NAMES.len())]
-            .copy_from_slice(&register_values);
+/// The contents of a `chunk_{i}.inputs` file, serialized as JSON.
+#[derive(Serialize, Deserialize)]
+struct ChunkInputsFile<Inputs> {
+    version: u32,
+    chunk_index: usize,
+    num_rows: u64,
+    bootloader_inputs: Inputs,
+}
+
+/// Writes the bootloader inputs and the number of rows of a chunk to
+/// `chunk_{chunk_index}.inputs` in `dir`, as a JSON object with the fields
+/// `version` (see `CHUNK_INPUTS_FORMAT_VERSION`), `chunk_index`, `num_rows`
+/// and `bootloader_inputs`.
+pub fn write_chunk_inputs<F: FieldElement>(
+    dir: &Path,
+    chunk_index: usize,
+    bootloader_inputs: &[F],
+    num_rows: u64,
+) -> std::io::Result<()> {
+    let file = File::create(dir.join(format!("chunk_{chunk_index}.inputs")))?;
+    let contents = ChunkInputsFile {
+        version: CHUNK_INPUTS_FORMAT_VERSION,
+        chunk_index,
+        num_rows,
+        bootloader_inputs,
+    };
+    serde_json::to_writer(BufWriter::new(file), &contents)?;
+    Ok(())
+}
+
+/// The register values at the end of a chunk, as written by `write_transition_registers`.
+#[derive(Serialize)]
+struct TransitionRegisters {
+    chunk: usize,
+    registers: BTreeMap<&'static str, String>,
+}
 
-        // Replace the updated root hash
-        let updated_root_hash_index = MEMORY_HASH_START_INDEX + 8;
-        bootloader_inputs[updated_root_hash_index..updated_root_hash_index + 8].copy_from_slice(
-            &merkle_tree
-                .root_hash()
+/// Writes the register values at the end of each chunk to `path`, as a JSON array
+/// with one object `{"chunk": i, "registers": {"main::x1": "0", ...}}` per chunk.
+/// The values are field elements, written as decimal strings. `register_values`
+/// contains the values of each chunk in the order of `REGISTER_MEMORY_NAMES`
+/// followed by `REGISTER_NAMES`, as returned by the dry run.
+pub fn write_transition_registers<F: FieldElement>(
+    path: &Path,
+    register_values: &[Vec<F>],
+) -> std::io::Result<()> {
+    let contents = register_values
+        .iter()
+        .enumerate()
+        .map(|(chunk, values)| TransitionRegisters {
+            chunk,
+            registers: REGISTER_MEMORY_NAMES
                 .iter()
-                .flat_map(|e| split_fe(*e))
-                .collect::<Vec<_>>(),
-        );
+                .chain(REGISTER_NAMES.iter())
+                .zip_eq(values)
+                .map(|(name, value)| (*name, value.to_arbitrary_integer().to_string()))
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &contents)?;
+    writer.flush()
+}
 
-        log::info!(
-            "Initial memory root hash: {}",
-            render_memory_hash(
-                &bootloader_inputs[MEMORY_HASH_START_INDEX..MEMORY_HASH_START_INDEX + 8]
-            )
-        );
-        log::info!(
-            "Final memory root hash: {}",
-            render_memory_hash(
-                &bootloader_inputs[MEMORY_HASH_START_INDEX + 8..MEMORY_HASH_START_INDEX + 16]
-            )
-        );
+/// Reads a file written by `write_chunk_inputs` and returns the bootloader
+/// inputs and the number of rows of the chunk.
+pub fn read_chunk_inputs<F: FieldElement>(path: &Path) -> Result<(Vec<F>, u64), String> {
+    let file = File::open(path).map_err(|e| format!("Cannot open {}: {e}", path.display()))?;
+    let contents: ChunkInputsFile<Vec<F>> = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format!("Cannot parse {}: {e}", path.display()))?;
+    if contents.version != CHUNK_INPUTS_FORMAT_VERSION {
+        return Err(format!(
+            "{} has format version {}, but only version {CHUNK_INPUTS_FORMAT_VERSION} is supported",
+            path.display(),
+            contents.version
+        ));
+    }
+    Ok((contents.bootloader_inputs, contents.num_rows))
+}
 
-        let actual_num_rows = chunk_exec.trace_len;
-        let bootloader_pc = bootloader_inputs[PC_INDEX];
-        bootloader_inputs_and_num_rows.push((bootloader_inputs, actual_num_rows as u64));
+/// Returns the first row of `trace` at which the pc is equal to `pc`.
+fn find_pc<F: FieldElement>(
+    trace: &HashMap<String, Vec<F>>,
+    pc: F,
+    trace_name: impl FnOnce() -> String,
+) -> Result<usize, ContinuationError<F>> {
+    let pcs = &trace["main::pc"];
+    pcs.iter()
+        .position(|&p| p == pc)
+        .ok_or_else(|| ContinuationError::PcNotReached {
+            trace: trace_name(),
+            pc,
+            observed_pcs: pcs.iter().cloned().collect(),
+        })
+}
 
-        log::info!("Chunk trace length: {}", chunk_exec.trace_len);
-        log::info!("Validating chunk...");
-        log::info!("Looking for pc = {}...", bootloader_pc);
-        let (start, _) = chunk_exec.trace["main::pc"]
-            .iter()
-            .enumerate()
-            .find(|(_, &pc)| pc == bootloader_pc)
-            .unwrap();
-        log::info!("Bootloader used {} rows.", start);
-        log::info!(
-            "  => {} / {} ({}%) of rows are used for the actual computation!",
-            length - start - shutdown_routine_rows,
+/// The registers that are compared between the chunk traces and the full trace.
+const VALIDATED_REGISTERS: [&str; 3] = ["main::pc", "main::query_arg_1", "main::query_arg_2"];
+
+/// How field elements are formatted in error messages about differing traces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValueFormat {
+    #[default]
+    Decimal,
+    Hex,
+    /// Elements in the upper half of the field are shown as negative numbers.
+    Signed,
+}
+
+impl ValueFormat {
+    pub fn format<F: FieldElement>(self, value: F) -> String {
+        match self {
+            ValueFormat::Decimal => value.to_string(),
+            ValueFormat::Hex => format!("{value:#x}"),
+            ValueFormat::Signed if value.is_in_lower_half() => value.to_string(),
+            ValueFormat::Signed => format!("-{}", -value),
+        }
+    }
+}
+
+/// Describes where and how much a chunk trace diverges from the full trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DivergenceReport {
+    /// Offset of the first differing row, relative to the start of the comparison.
+    pub first_offset: usize,
+    /// The registers that differ at `first_offset`.
+    pub diverging_registers: Vec<String>,
+    /// The number of compared rows in which at least one register differs.
+    pub total_diverging_rows: usize,
+    /// The first differing register with its index in the register values of the
+    /// bootloader inputs and both values, formatted according to `DryRunOptions::value_format`.
+    pub first_difference: String,
+}
+
+impl std::fmt::Display for DivergenceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "first difference at offset {} in register(s) {} ({}), {} differing rows in total",
+            self.first_offset,
+            self.diverging_registers.join(", "),
+            self.first_difference,
+            self.total_diverging_rows
+        )
+    }
+}
+
+/// Checks that the rows `start..end` of the chunk trace equal the full trace,
+/// starting at row `proven_trace`, or match it according to `compare`, if given.
+fn validate_chunk<F: FieldElement>(
+    chunk_trace: &HashMap<String, Vec<F>>,
+    full_trace: &HashMap<String, Vec<F>>,
+    end: usize,
+    start: usize,
+    proven_trace: usize,
+    value_format: ValueFormat,
+    compare: Option<&dyn Fn(&str, usize, F, F) -> bool>,
+) -> Result<(), DivergenceReport> {
+    let diverging_registers_at = |i: usize| -> Vec<&'static str> {
+        VALIDATED_REGISTERS
+            .iter()
+            .copied()
+            .filter(|reg| {
+                let chunk_value = chunk_trace[*reg][i + start];
+                let full_value = full_trace[*reg][i + proven_trace];
+                match compare {
+                    Some(compare) => !compare(reg, i, chunk_value, full_value),
+                    None => chunk_value != full_value,
+                }
+            })
+            .collect()
+    };
+
+    let num_compared_rows = end - start;
+    let Some(first_offset) =
+        (0..num_compared_rows).find(|&i| !diverging_registers_at(i).is_empty())
+    else {
+        return Ok(());
+    };
+
+    let chunk_i = first_offset + start;
+    let full_i = first_offset + proven_trace;
+    let diverging_registers = diverging_registers_at(first_offset);
+    let reg = diverging_registers[0];
+    let reg_index = REGISTER_MEMORY_NAMES
+        .iter()
+        .chain(&REGISTER_NAMES)
+        .position(|r| *r == reg)
+        .unwrap();
+    let first_difference = format!(
+        "{reg} (register {reg_index}): {} != {}",
+        value_format.format(chunk_trace[reg][chunk_i]),
+        value_format.format(full_trace[reg][full_i]),
+    );
+    tracing::error!("The Chunk trace differs from the full trace!");
+    tracing::error!(
+        "Started comparing from row {start} in the chunk to row {proven_trace} in the full trace; the difference is at offset {first_offset}."
+    );
+    tracing::error!(
+        "The PCs are {} and {}.",
+        chunk_trace["main::pc"][chunk_i],
+        full_trace["main::pc"][full_i]
+    );
+    tracing::error!("The first difference is in register {first_difference}");
+
+    let report = DivergenceReport {
+        first_offset,
+        diverging_registers: diverging_registers
+            .into_iter()
+            .map(|r| r.to_string())
+            .collect(),
+        total_diverging_rows: (first_offset..num_compared_rows)
+            .filter(|&i| !diverging_registers_at(i).is_empty())
+            .count(),
+        first_difference,
+    };
+    tracing::error!("Divergence report: {report}");
+    Err(report)
+}
+
+/// The number of rows before and after the compared rows to include in the trace dumps.
+const TRACE_DUMP_CONTEXT_ROWS: usize = 5;
+
+/// Corresponding rows of a chunk trace and the full trace.
+struct TraceWindow {
+    chunk_start: usize,
+    full_start: usize,
+    len: usize,
+}
+
+/// Writes the rows of `window`, extended by `TRACE_DUMP_CONTEXT_ROWS` in both directions,
+/// of all columns of both traces to `chunk_{i}_chunk.trace` and `chunk_{i}_full.trace`.
+/// Each line contains the offset relative to the start of the window, the column
+/// name and the value, so that the two files can be compared with a diff tool.
+fn write_trace_dump<F: FieldElement>(
+    dir: &Path,
+    chunk_index: usize,
+    chunk_trace: &HashMap<String, Vec<F>>,
+    full_trace: &HashMap<String, Vec<F>>,
+    window: TraceWindow,
+    value_format: ValueFormat,
+) -> std::io::Result<()> {
+    // Both files start at the same offset, so that corresponding rows have the same offset.
+    let before = TRACE_DUMP_CONTEXT_ROWS
+        .min(window.chunk_start)
+        .min(window.full_start);
+    for (trace, start, suffix) in [
+        (chunk_trace, window.chunk_start, "chunk"),
+        (full_trace, window.full_start, "full"),
+    ] {
+        let mut out = BufWriter::new(File::create(
+            dir.join(format!("chunk_{chunk_index}_{suffix}.trace")),
+        )?);
+        let columns = trace.iter().sorted_by_key(|(name, _)| *name).collect_vec();
+        for row in (start - before)..(start + window.len + TRACE_DUMP_CONTEXT_ROWS) {
+            let offset = row as i64 - start as i64;
+            for (name, values) in &columns {
+                if let Some(value) = values.get(row) {
+                    writeln!(out, "{offset} {name} {}", value_format.format(*value))?;
+                }
+            }
+        }
+        out.flush()?;
+    }
+    Ok(())
+}
+
+/// Executes the whole program once, without paging in any memory through the bootloader.
+///
+/// This needs the tracing mode of the executor, even if the chunks are not validated:
+/// The dry run needs the `main::pc` column to find the first row of the actual
+/// computation and the memory accesses to find the pages accessed by each chunk,
+/// and the fast mode of the executor records neither. Use `fast_trace_length`
+/// if only (an estimate of) the trace length is needed.
+fn execute_full_trace<F: FieldElement>(
+    asm: &AnalysisASMFile,
+    pil: &Analyzed<F>,
+    fixed: FixedColumns<F>,
+    initial_memory: MemoryState,
+    pipeline: &Pipeline<F>,
+    profiler_opt: Option<ProfilerOptions>,
+    max_steps: Option<usize>,
+) -> Execution<F> {
+    powdr_riscv_executor::execute::<F>(
+        asm,
+        pil,
+        fixed,
+        initial_memory,
+        pipeline.data_callback().unwrap(),
+        // Run full trace without any accessed pages. This would actually violate the
+        // constraints, but the executor does the right thing (read zero if the memory
+        // cell has never been accessed). We can't pass the accessed pages here, because
+        // we only know them after the full trace has been generated.
+        &default_input(&[]),
+        max_steps,
+        profiler_opt,
+    )
+}
+
+/// Executes a single chunk, starting from zeroed memory and the state
+/// given by `bootloader_inputs`, for at most `num_rows` rows.
+fn execute_chunk<F: FieldElement>(
+    asm: &AnalysisASMFile,
+    pil: &Analyzed<F>,
+    fixed: FixedColumns<F>,
+    pipeline: &Pipeline<F>,
+    bootloader_inputs: &[F],
+    initial_memory: MemoryState,
+    num_rows: usize,
+) -> Execution<F> {
+    powdr_riscv_executor::execute::<F>(
+        asm,
+        pil,
+        fixed,
+        initial_memory,
+        pipeline.data_callback().unwrap(),
+        bootloader_inputs,
+        Some(num_rows),
+        // profiling was done when full trace was generated
+        None,
+    )
+}
+
+/// Returns the number of rows used by the bootloader of a chunk with the given inputs.
+/// The chunk is executed for increasing numbers of rows, but at most `num_rows`, until
+/// the computation starts, which is much cheaper than executing the whole chunk.
+fn bootloader_rows<F: FieldElement>(
+    asm: &AnalysisASMFile,
+    pil: &Analyzed<F>,
+    fixed: &FixedColumns<F>,
+    pipeline: &Pipeline<F>,
+    bootloader_inputs: &[F],
+    num_rows: usize,
+    chunk_index: usize,
+) -> Result<usize, ContinuationError<F>> {
+    let num_pages = bootloader_inputs[NUM_PAGES_INDEX].to_degree() as usize;
+    // The bootloader does at least as much work per page as the shutdown routine.
+    let mut rows = shutdown_routine_upper_bound(num_pages).min(num_rows);
+    loop {
+        let chunk_exec = execute_chunk(
+            asm,
+            pil,
+            fixed.clone(),
+            pipeline,
+            bootloader_inputs,
+            MemoryState::new(),
+            rows,
+        );
+        match find_pc(&chunk_exec.trace, bootloader_inputs[PC_INDEX], || {
+            format!("trace of chunk {chunk_index}")
+        }) {
+            Ok(start) => return Ok(start),
+            Err(e) if rows == num_rows => return Err(e),
+            Err(_) => rows = (rows * 2).min(num_rows),
+        }
+    }
+}
+
+/// The trace of a single chunk, see `debug_chunk`.
+pub struct DetailedChunkTrace<F: FieldElement> {
+    /// The trace of the main machine, by column name.
+    pub trace: HashMap<String, Vec<F>>,
+    /// The number of rows of the trace.
+    pub trace_len: usize,
+    /// The number of rows used by the bootloader, i.e. the index of the first
+    /// row of the actual computation.
+    pub bootloader_rows: usize,
+}
+
+/// Re-executes a single chunk, given its bootloader inputs and number of rows
+/// as returned by the dry run (see `DryRunResult::bootloader_inputs`), and
+/// returns its trace. Useful to inspect a chunk reported by the validation
+/// without running the whole dry run again.
+pub fn debug_chunk<F: FieldElement>(
+    pipeline: &mut Pipeline<F>,
+    bootloader_inputs: &[F],
+    num_rows: u64,
+) -> Result<DetailedChunkTrace<F>, ContinuationError<F>> {
+    let asm = pipeline.compute_analyzed_asm().unwrap().clone();
+    let pil = pipeline.compute_optimized_pil().unwrap();
+    let fixed = pipeline.compute_fixed_cols().unwrap();
+
+    let chunk_exec = execute_chunk(
+        &asm,
+        &pil,
+        fixed,
+        pipeline,
+        bootloader_inputs,
+        MemoryState::new(),
+        num_rows as usize,
+    );
+    let bootloader_rows = find_pc(&chunk_exec.trace, bootloader_inputs[PC_INDEX], || {
+        "trace of the chunk".to_string()
+    })?;
+
+    Ok(DetailedChunkTrace {
+        trace: chunk_exec.trace,
+        trace_len: chunk_exec.trace_len,
+        bootloader_rows,
+    })
+}
+
+/// Executes the whole program in the fast mode of the executor and returns the
+/// trace length. This is much faster and uses less memory than the dry run, but
+/// the length does not include the few rows the tracing mode appends at the end
+/// of the execution, so it can be slightly smaller than `DryRunResult::trace_len`.
+/// Useful to quickly estimate the number of chunks.
+pub fn fast_trace_length<F: FieldElement>(pipeline: &mut Pipeline<F>) -> usize {
+    let asm = pipeline.compute_analyzed_asm().unwrap().clone();
+    let initial_memory = load_initial_memory(&asm);
+    powdr_riscv_executor::execute_fast::<F>(
+        &asm,
+        initial_memory,
+        pipeline.data_callback().unwrap(),
+        &default_input(&[]),
+        None,
+    )
+}
+
+/// Counts how often each instruction is executed in the given trace,
+/// using the `main::instr_<name>` flag columns.
+pub fn instruction_histogram<F: FieldElement>(
+    trace: &HashMap<String, Vec<F>>,
+) -> HashMap<String, u64> {
+    trace
+        .iter()
+        .filter_map(|(name, values)| {
+            let instruction = name.strip_prefix("main::instr_")?;
+            // Skip the columns of instruction parameters.
+            if instruction.contains("_param_") {
+                return None;
+            }
+            let count = values.iter().filter(|v| v.is_one()).count() as u64;
+            Some((instruction.to_string(), count))
+        })
+        .collect()
+}
+
+/// Returns the pages among the first `total_pages` pages that are never accessed
+/// during the execution of the program. Requires one run of the full trace.
+pub fn unaccessed_pages<F: FieldElement>(
+    pipeline: &mut Pipeline<F>,
+    total_pages: u64,
+) -> BTreeSet<u64> {
+    let asm = pipeline.compute_analyzed_asm().unwrap().clone();
+    let pil = pipeline.compute_optimized_pil().unwrap();
+    let fixed = pipeline.compute_fixed_cols().unwrap();
+    let initial_memory = load_initial_memory(&asm);
+
+    let full_exec = execute_full_trace(&asm, &pil, fixed, initial_memory, pipeline, None, None);
+    let accessed_pages = full_exec
+        .memory_accesses
+        .iter()
+        .map(|access| ContinuationMemoryAccess::from(access).page())
+        .collect::<BTreeSet<_>>();
+
+    (0..total_pages)
+        .filter(|page| !accessed_pages.contains(page))
+        .collect()
+}
+
+/// Checks that the program can be run with continuations, without computing
+/// the inputs of the chunks: The main machine has to pass the sanity checks
+/// and the full execution has to reach `DEFAULT_PC` after the bootloader.
+/// Much cheaper than a dry run, because neither the Merkle tree nor the chunks
+/// need to be computed.
+pub fn validate_continuation_compatible<F: FieldElement>(
+    pipeline: &mut Pipeline<F>,
+) -> Result<(), ContinuationError<F>> {
+    let field = F::known_field().unwrap();
+
+    let asm = pipeline.compute_analyzed_asm().unwrap().clone();
+    let pil = pipeline.compute_optimized_pil().unwrap();
+    let fixed = pipeline.compute_fixed_cols().unwrap();
+    let main_machine = asm.get_machine(&parse_absolute_path("::Main")).unwrap();
+    sanity_check::<F>(main_machine, field);
+    main_machine_max_degree(main_machine)?;
+
+    let initial_memory = load_initial_memory(&asm);
+    let full_exec = execute_full_trace(&asm, &pil, fixed, initial_memory, pipeline, None, None);
+    tracing::info!("Total trace length: {}", full_exec.trace_len);
+
+    let first_real_execution_row = find_pc(&full_exec.trace, DEFAULT_PC.into(), || {
+        "full trace".to_string()
+    })?;
+    tracing::info!("The bootloader of the full trace uses {first_real_execution_row} rows.");
+    Ok(())
+}
+
+/// The result of `continuation_sizing`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContinuationSizing {
+    /// The length of the full execution trace.
+    pub full_trace_length: usize,
+    /// The number of rows the bootloader takes in the full trace.
+    pub first_real_execution_row: usize,
+    /// The max degree of the main machine, i.e. the number of rows of each chunk.
+    pub degree: usize,
+    /// A lower bound on the number of chunks, see `continuation_sizing`.
+    pub min_num_chunks: usize,
+}
+
+/// Computes the size of the execution without the chunk executions or the Merkle tree,
+/// which makes it much cheaper than a dry run.
+///
+/// The number of chunks is only a lower bound: It assumes that each chunk spends as
+/// many rows in the bootloader as the full trace, and the least possible number of rows
+/// in the shutdown routine. The real chunks page in memory, which takes more rows
+/// depending on the number of accessed pages, so they can need more chunks.
+pub fn continuation_sizing<F: FieldElement>(
+    pipeline: &mut Pipeline<F>,
+) -> Result<ContinuationSizing, ContinuationError<F>> {
+    let asm = pipeline.compute_analyzed_asm().unwrap().clone();
+    let pil = pipeline.compute_optimized_pil().unwrap();
+    let fixed = pipeline.compute_fixed_cols().unwrap();
+    let main_machine = asm.get_machine(&parse_absolute_path("::Main")).unwrap();
+    let degree = main_machine_max_degree(main_machine)?;
+
+    let initial_memory = load_initial_memory(&asm);
+    let full_exec = execute_full_trace(&asm, &pil, fixed, initial_memory, pipeline, None, None);
+    let first_real_execution_row = find_pc(&full_exec.trace, DEFAULT_PC.into(), || {
+        "full trace".to_string()
+    })?;
+
+    // The same arithmetic as in the dry run, with the smallest possible
+    // bootloader and shutdown routine.
+    let num_rows = degree - shutdown_routine_upper_bound(0);
+    assert!(
+        num_rows > first_real_execution_row + 1,
+        "The degree of the main machine is too small for continuations."
+    );
+    let mut proven_trace = first_real_execution_row;
+    let mut min_num_chunks = 1;
+    while full_exec.trace_len - proven_trace + first_real_execution_row >= num_rows {
+        proven_trace += num_rows - first_real_execution_row - 1;
+        min_num_chunks += 1;
+    }
+
+    Ok(ContinuationSizing {
+        full_trace_length: full_exec.trace_len,
+        first_real_execution_row,
+        degree,
+        min_num_chunks,
+    })
+}
+
+/// Returns the pages accessed in the sorted `memory_accesses` from row `proven_trace`
+/// of the full trace on, up to the last row that a chunk of `length` rows could reach.
+/// Fails if a page is outside of the memory covered by the Merkle tree.
+fn pages_accessed_by_chunk<F: FieldElement>(
+    memory_accesses: &[ContinuationMemoryAccess],
+    proven_trace: usize,
+    length: usize,
+) -> Result<BTreeSet<u32>, ContinuationError<F>> {
+    let mut accessed_pages = BTreeSet::new();
+    let mut accessed_addresses = BTreeSet::new();
+
+    let start_idx = memory_accesses
+        .binary_search_by_key(&proven_trace, |a| a.row)
+        .unwrap_or_else(|v| v);
+
+    for access in &memory_accesses[start_idx..] {
+        // proven_trace + length is an upper bound for the last row index we'll reach in the next chunk.
+        // In practice, we'll stop earlier, because the bootloader & shutdown routine need to run as well,
+        // but we don't know for how long as that depends on the number of pages.
+        if access.row >= proven_trace + length {
+            break;
+        }
+        let origin = PageOrigin::Access {
+            row: access.row,
+            address: access.address,
+        };
+        accessed_pages.insert(page_index(access.page(), origin)?);
+        accessed_addresses.insert(access.address);
+    }
+    tracing::info!(
+        "{} unique memory accesses over {} accessed pages: {:?}",
+        accessed_addresses.len(),
+        accessed_pages.len(),
+        accessed_pages
+    );
+    Ok(accessed_pages)
+}
+
+/// Returns the address in register memory of the register with the given name in
+/// `REGISTER_MEMORY_NAMES`.
+fn register_memory_address(name: &str) -> u32 {
+    Register::from(name.strip_prefix("main::").unwrap()).addr() as u32
+}
+
+/// Replaces the values in `bootloader_inputs` that describe the state at the end of the
+/// chunk (see `bootloader::create_input`) by the actual values after the execution of the
+/// chunk, i.e. the Merkle proofs and hashes of the accessed pages, the register values and
+/// the memory root hash. Updates `merkle_tree` with the memory of the chunk on the way.
+/// Returns the register values at the end of the chunk.
+fn update_bootloader_inputs<F: FieldElement>(
+    bootloader_inputs: &mut [F],
+    merkle_tree: &mut MerkleTree<F>,
+    accessed_pages: &BTreeSet<u32>,
+    kept_pages: &BTreeSet<u32>,
+    chunk_exec: &mut Execution<F>,
+) -> Vec<F> {
+    update_page_inputs(
+        bootloader_inputs,
+        merkle_tree,
+        accessed_pages,
+        kept_pages,
+        std::mem::take(&mut chunk_exec.memory),
+    );
+
+    // Go over all memory registers
+    let mut register_values = REGISTER_MEMORY_NAMES
+        .iter()
+        .map(|reg| {
+            *chunk_exec
+                .register_memory
+                .get(&register_memory_address(reg))
+                .unwrap_or(&0.into())
+        })
+        .collect::<Vec<_>>();
+
+    // Go over all machine registers
+    for reg in REGISTER_NAMES {
+        register_values.push(*chunk_exec.trace[reg].last().unwrap());
+    }
+
+    // Replace final register values of the current chunk
+    bootloader_inputs[(REGISTER_MEMORY_NAMES.len() + REGISTER_NAMES.len())
+        ..2 * (REGISTER_MEMORY_NAMES.len() + REGISTER_NAMES.len())]
+        .copy_from_slice(&register_values);
+
+    register_values
+}
+
+/// Replaces the Merkle proofs and hashes of the accessed pages and the memory root hash
+/// in `bootloader_inputs` by the values after applying `memory_updates`, which are
+/// applied to `merkle_tree` on the way. The updates of `kept_pages`, which the chunk
+/// keeps in memory instead of paging them (see `DryRunOptions::always_resident_pages`),
+/// are only applied to `merkle_tree`. Part of `update_bootloader_inputs`.
+fn update_page_inputs<F: FieldElement>(
+    bootloader_inputs: &mut [F],
+    merkle_tree: &mut MerkleTree<F>,
+    accessed_pages: &BTreeSet<u32>,
+    kept_pages: &BTreeSet<u32>,
+    memory_updates: MemoryState,
+) {
+    let mut memory_updates_by_page =
+        merkle_tree.organize_updates_by_page(memory_updates.into_iter());
+    for (i, &page_index) in accessed_pages.iter().enumerate() {
+        let page_index = page_index as usize;
+        let (_, _, proof) = merkle_tree.get(page_index);
+
+        // Replace the proof
+        let proof_start_index =
+            PAGE_INPUTS_OFFSET + BOOTLOADER_INPUTS_PER_PAGE * i + 1 + WORDS_PER_PAGE + 8;
+        for (j, sibling) in proof.into_iter().enumerate() {
+            bootloader_inputs[proof_start_index + j * 8..proof_start_index + j * 8 + 8]
+                .copy_from_slice(
+                    &sibling
+                        .iter()
+                        .flat_map(|e| split_fe(*e))
+                        .collect::<Vec<_>>(),
+                );
+        }
+
+        // Update one child of the Merkle tree
+        merkle_tree.update_page(
+            page_index,
+            &memory_updates_by_page
+                .remove(&page_index)
+                .unwrap_or_default(),
+        );
+
+        let (_, page_hash, proof) = merkle_tree.get(page_index);
+
+        // Assert the proof hasn't changed (because we didn't update any page except the current).
+        for (j, sibling) in proof.into_iter().enumerate() {
+            assert_eq!(
+                &bootloader_inputs[proof_start_index + j * 8..proof_start_index + j * 8 + 8],
+                sibling
+                    .iter()
+                    .flat_map(|e| split_fe(*e))
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        // Replace the page hash
+        let updated_page_hash_index =
+            PAGE_INPUTS_OFFSET + BOOTLOADER_INPUTS_PER_PAGE * i + 1 + WORDS_PER_PAGE;
+        bootloader_inputs[updated_page_hash_index..updated_page_hash_index + 8].copy_from_slice(
+            &page_hash
+                .iter()
+                .flat_map(|e| split_fe(*e))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    for &page_index in kept_pages {
+        let page_index = page_index as usize;
+        if let Some(updates) = memory_updates_by_page.remove(&page_index) {
+            merkle_tree.update_page(page_index, &updates);
+        }
+    }
+
+    // Replace the updated root hash
+    let updated_root_hash_index = MEMORY_HASH_START_INDEX + 8;
+    bootloader_inputs[updated_root_hash_index..updated_root_hash_index + 8].copy_from_slice(
+        &merkle_tree
+            .root_hash()
+            .iter()
+            .flat_map(|e| split_fe(*e))
+            .collect::<Vec<_>>(),
+    );
+}
+
+/// The rows of a chunk, computed from the number of rows used by its bootloader
+/// without executing the chunk, see `ContinuationDryRun::chunk_layout`.
+#[derive(Clone)]
+struct ChunkLayout {
+    accessed_pages: BTreeSet<u32>,
+    /// The number of rows the chunk is run for.
+    num_rows: usize,
+    /// The length of the chunk trace.
+    trace_len: usize,
+    bootloader_rows: usize,
+    rows: ChunkRows,
+}
+
+/// A chunk processed by `ContinuationDryRun::step`.
+struct ChunkOutput<F: FieldElement> {
+    bootloader_inputs: Vec<F>,
+    stats: ChunkStats,
+    is_last_chunk: bool,
+}
+
+/// Computes the bootloader inputs of the chunks one at a time, as an iterator over the
+/// bootloader inputs and the number of rows of each chunk (like the entries of
+/// `DryRunResult::bootloader_inputs`).
+///
+/// This allows to start proving the first chunk before the inputs of the later chunks
+/// are computed, and to keep only the inputs of one chunk in memory. The chunks are
+/// processed like in `rust_continuations_dry_run_with_options`, which uses this iterator,
+/// so the `DryRunOptions` apply as well, except for the ones that only affect the
+/// `DryRunResult` or are about cancellation.
+///
+/// Note that the state at the start of a chunk depends on all previous chunks: Their
+/// memory writes update the Merkle tree, and the number of bootloader rows of each chunk
+/// determines where the next chunk starts in the full trace. `Iterator::nth` does not
+/// execute the skipped chunks, but it still has to build the inputs of each of them and
+/// run its bootloader to find the rows it proves, and then applies the memory and
+/// register writes of the full trace in these rows to the state. So `nth(42)` is much
+/// cheaper than computing the inputs of chunks 0 to 41, but still takes time linear in
+/// the number of skipped chunks.
+pub struct ContinuationDryRun<'a, 'o, F: FieldElement> {
+    pipeline: &'a Pipeline<F>,
+    options: DryRunOptions<'o, F>,
+    asm: AnalysisASMFile,
+    pil: Arc<Analyzed<F>>,
+    fixed: FixedColumns<F>,
+    full_exec: Execution<F>,
+    memory_accesses: Vec<ContinuationMemoryAccess>,
+    always_resident_pages: BTreeSet<u32>,
+    /// The layouts of the chunks in `DryRunOptions::resume_from`.
+    resumed_chunks: Vec<ChunkLayout>,
+    /// The pc the last chunk is expected to end at, if known.
+    halt_pc: Option<u64>,
+    /// The max degree of the main machine.
+    length: usize,
+    merkle_tree: MerkleTree<F>,
+    /// The register values at the start of the next chunk.
+    register_values: Vec<F>,
+    /// The number of rows of the full trace that the previous chunks prove.
+    proven_trace: usize,
+    chunk_index: usize,
+    done: bool,
+}
+
+impl<'a, 'o, F: FieldElement> ContinuationDryRun<'a, 'o, F> {
+    /// Executes the full trace, the chunks are executed by the iterator.
+    pub fn new(
+        pipeline: &'a mut Pipeline<F>,
+        profiler_opt: Option<ProfilerOptions>,
+    ) -> Result<Self, ContinuationError<F>> {
+        Self::with_options(pipeline, profiler_opt, Default::default())
+    }
+
+    /// Same as `new`, but customizable via `options`.
+    pub fn with_options(
+        pipeline: &'a mut Pipeline<F>,
+        profiler_opt: Option<ProfilerOptions>,
+        mut options: DryRunOptions<'o, F>,
+    ) -> Result<Self, ContinuationError<F>> {
+        let field = F::known_field().unwrap();
+
+        let default_pc = options.default_pc.unwrap_or(DEFAULT_PC);
+
+        // Initial register values for the first chunk.
+        let mut register_values = default_register_values();
+        register_values[PC_INDEX] = default_pc.into();
+
+        let asm = pipeline.compute_analyzed_asm().unwrap().clone();
+        let pil = pipeline.compute_optimized_pil().unwrap();
+        let fixed = pipeline.compute_fixed_cols().unwrap();
+        let main_machine = asm.get_machine(&parse_absolute_path("::Main")).unwrap();
+        sanity_check::<F>(main_machine, field);
+        let length = main_machine_max_degree(main_machine)?;
+        let halt_pc = options
+            .expected_halt_pc
+            .or_else(|| default_halt_pc(main_machine));
+
+        let always_resident_pages = options
+            .always_resident_pages
+            .iter()
+            .map(|&page| page_index(page, PageOrigin::AlwaysResident))
+            .collect::<Result<BTreeSet<_>, _>>()?;
+
+        tracing::info!("Initializing memory merkle tree...");
+
+        // Get initial memory contents from the special variable "initial_memory".
+        // In the first full run, we use it as the memory contents of the executor;
+        // on the independent chunk runs, the executor uses zeroed initial memory,
+        // and the pages are loaded via the bootloader.
+        let initial_memory = load_initial_memory(&asm);
+
+        let mut merkle_tree = MerkleTree::<F>::new();
+        merkle_tree.update(initial_memory.iter().map(|(k, v)| (*k, *v)));
+
+        // TODO: commit to the merkle_tree root in the verifier.
+
+        tracing::info!("Initial execution...");
+        let full_exec = {
+            let _span = tracing::info_span!("full_trace").entered();
+            execute_full_trace(
+                &asm,
+                &pil,
+                fixed.clone(),
+                initial_memory,
+                pipeline,
+                profiler_opt,
+                // One more row than allowed, so that a program that needs all of them
+                // can be told apart from one that needs more.
+                options
+                    .max_steps
+                    .map(|steps| steps.saturating_add(FINAL_SINK_ROWS + 1)),
+            )
+        };
+
+        let full_trace_length = full_exec.trace_len;
+        if let Some(steps) = options
+            .max_steps
+            .filter(|&steps| full_trace_length.saturating_sub(FINAL_SINK_ROWS) > steps)
+        {
+            return Err(ContinuationError::NonTerminating { steps });
+        }
+        tracing::info!("Total trace length: {}", full_trace_length);
+
+        let memory_accesses = match options.memory_accesses.take() {
+            Some(accesses) => {
+                validate_memory_accesses(&accesses, full_trace_length)
+                    .map_err(ContinuationError::InvalidMemoryAccesses)?;
+                accesses
+            }
+            None => full_exec
+                .memory_accesses
+                .iter()
+                .map(ContinuationMemoryAccess::from)
+                .collect(),
+        };
+
+        // The number of rows of the full trace that we consider proven.
+        // Initialized with `first_real_execution_row`, because the bootloader
+        // execution in the first chunk will be different from the full trace
+        // execution (because of paged-in memory).
+        let first_real_execution_row = find_pc(&full_exec.trace, default_pc.into(), || {
+            "full trace".to_string()
+        })?;
+
+        if let Some(out) = options.stats_csv.as_mut() {
+            writeln!(out, "{}", ChunkStats::CSV_HEADER).map_err(ContinuationError::WriteStats)?;
+        }
+
+        let mut dry_run = Self {
+            pipeline,
+            options,
+            asm,
+            pil,
+            fixed,
+            full_exec,
+            memory_accesses,
+            always_resident_pages,
+            resumed_chunks: vec![],
+            halt_pc,
+            length,
+            merkle_tree,
+            register_values,
+            proven_trace: first_real_execution_row,
+            chunk_index: 0,
+            done: false,
+        };
+        dry_run.resumed_chunks = dry_run.resumed_chunk_layouts()?;
+        Ok(dry_run)
+    }
+
+    /// Computes the layouts of the chunks in `DryRunOptions::resume_from`. They only
+    /// depend on the full trace and the inputs, so too many resumed chunks are rejected
+    /// before any chunk is processed.
+    fn resumed_chunk_layouts(&self) -> Result<Vec<ChunkLayout>, ContinuationError<F>> {
+        let mut layouts: Vec<ChunkLayout> = vec![];
+        let mut proven_trace = self.proven_trace;
+        for (chunk_index, resumed_inputs) in self.options.resume_from.iter().enumerate() {
+            if layouts
+                .last()
+                .is_some_and(|layout| layout.rows.is_last_chunk)
+            {
+                return Err(ContinuationError::TooManyResumedChunks {
+                    provided: self.options.resume_from.len(),
+                    actual: chunk_index,
+                });
+            }
+            let accessed_pages = self.accessed_pages(proven_trace)?;
+            if resumed_inputs.len()
+                != PAGE_INPUTS_OFFSET + BOOTLOADER_INPUTS_PER_PAGE * accessed_pages.len()
+            {
+                return Err(ContinuationError::ResumeMismatch { chunk_index });
+            }
+            let layout =
+                self.chunk_layout(chunk_index, proven_trace, accessed_pages, resumed_inputs)?;
+            proven_trace += layout.rows.new_rows;
+            layouts.push(layout);
+        }
+        Ok(layouts)
+    }
+
+    /// Returns the pages paged in by the chunk that starts at row `proven_trace` of the
+    /// full trace: The first chunk pages in the always resident pages together with
+    /// the ones it accesses, later chunks keep them in memory (see `kept_pages`).
+    fn accessed_pages(&self, proven_trace: usize) -> Result<BTreeSet<u32>, ContinuationError<F>> {
+        let mut accessed_pages =
+            pages_accessed_by_chunk(&self.memory_accesses, proven_trace, self.length)?;
+        match proven_trace {
+            0 => accessed_pages.extend(&self.always_resident_pages),
+            _ => accessed_pages.retain(|page| !self.always_resident_pages.contains(page)),
+        }
+        Ok(accessed_pages)
+    }
+
+    /// Returns the always resident pages that the chunk with the given index keeps in
+    /// memory from the previous chunk instead of paging them in.
+    fn kept_pages(&self, chunk_index: usize) -> BTreeSet<u32> {
+        match chunk_index {
+            0 => BTreeSet::new(),
+            _ => self.always_resident_pages.clone(),
+        }
+    }
+
+    /// Returns the current memory of the given pages, which a chunk that keeps them
+    /// starts with.
+    fn kept_memory(&self, pages: &BTreeSet<u32>) -> MemoryState {
+        pages
+            .iter()
+            .flat_map(|&page| {
+                let (words, _, _) = self.merkle_tree.get(page as usize);
+                words
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, word)| **word != 0.into())
+                    .map(move |(i, word)| {
+                        (
+                            (page << PAGE_SIZE_BYTES_LOG) + (i * BYTES_PER_WORD) as u32,
+                            word.to_degree() as u32,
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    /// Computes the layout of the chunk with the given inputs that starts at row
+    /// `proven_trace` of the full trace, only running its bootloader.
+    fn chunk_layout(
+        &self,
+        chunk_index: usize,
+        proven_trace: usize,
+        accessed_pages: BTreeSet<u32>,
+        bootloader_inputs: &[F],
+    ) -> Result<ChunkLayout, ContinuationError<F>> {
+        let mut num_rows = self.length - shutdown_routine_upper_bound(accessed_pages.len());
+        let bootloader_rows = bootloader_rows(
+            &self.asm,
+            &self.pil,
+            &self.fixed,
+            self.pipeline,
+            bootloader_inputs,
+            num_rows,
+            chunk_index,
+        )?;
+        // The chunk runs until the end of the execution or until it is full.
+        let trace_len = |num_rows: usize| {
+            (bootloader_rows + self.full_exec.trace_len - proven_trace).min(num_rows)
+        };
+        if let Some(boundaries) = self.options.prefer_boundaries {
+            if trace_len(num_rows) == num_rows {
+                if let Some(rows) = rows_until_preferred_boundary(
+                    boundaries,
+                    self.options.boundary_slack,
+                    proven_trace,
+                    bootloader_rows,
+                    num_rows,
+                ) {
+                    num_rows = rows;
+                }
+            }
+        }
+        let trace_len = trace_len(num_rows);
+        let rows = chunk_rows(trace_len, bootloader_rows, num_rows).ok_or(
+            ContinuationError::NoProgress {
+                chunk_index,
+                chunk_trace_len: trace_len,
+                bootloader_rows,
+                num_rows,
+            },
+        )?;
+        Ok(ChunkLayout {
+            accessed_pages,
+            num_rows,
+            trace_len,
+            bootloader_rows,
+            rows,
+        })
+    }
+
+    /// Advances the Merkle tree and the register values over the next chunk without
+    /// executing it, by applying the memory and register writes of the full trace in the
+    /// rows of `layout`. Completes and returns `bootloader_inputs`, the inputs of the
+    /// chunk as built by `bootloader::create_input`. Fails if the chunk is resumed and
+    /// its inputs in `DryRunOptions::resume_from` differ.
+    fn fast_forward(
+        &mut self,
+        layout: &ChunkLayout,
+        mut bootloader_inputs: Vec<F>,
+    ) -> Result<Vec<F>, ContinuationError<F>> {
+        // The chunk ends with the row of the full trace at which the next chunk continues
+        // and which both chunks execute. The memory of the chunk includes the writes of
+        // that row, but its register memory is the one the next chunk starts with, i.e.
+        // before that row (see `Execution::memory` and `RegisterMemory::for_bootloader` in
+        // the executor). The first chunk also includes the bootloader rows of the full
+        // trace, and the last chunk ends like the full trace.
+        let next_chunk_row = self.proven_trace + layout.rows.new_rows;
+        let first_row = match self.chunk_index {
+            0 => 0,
+            _ => self.proven_trace + 1,
+        };
+        // The executor records the writes of a row with the index of the next row.
+        let memory_rows = match layout.rows.is_last_chunk {
+            true => first_row..=usize::MAX,
+            false => first_row..=next_chunk_row + 1,
+        };
+        let register_rows = first_row..=next_chunk_row;
+
+        let memory_accesses = &self.full_exec.memory_accesses;
+        let memory_updates = memory_accesses
+            [memory_accesses.partition_point(|op| op.row < first_row)..]
+            .iter()
+            .take_while(|op| memory_rows.contains(&op.row))
+            .filter(|op| matches!(op.kind, MemOperationKind::Write))
+            .map(|op| (op.address, op.value))
+            .collect();
+        update_page_inputs(
+            &mut bootloader_inputs,
+            &mut self.merkle_tree,
+            &layout.accessed_pages,
+            &self.kept_pages(self.chunk_index),
+            memory_updates,
+        );
+
+        if layout.rows.is_last_chunk {
+            for (value, reg) in self.register_values.iter_mut().zip(REGISTER_MEMORY_NAMES) {
+                *value = *self
+                    .full_exec
+                    .register_memory
+                    .get(&register_memory_address(reg))
+                    .unwrap_or(&0.into());
+            }
+        } else {
+            let register_indices: HashMap<u32, usize> = REGISTER_MEMORY_NAMES
+                .iter()
+                .enumerate()
+                .map(|(i, reg)| (register_memory_address(reg), i))
+                .collect();
+            let writes = &self.full_exec.register_memory_writes;
+            for write in writes[writes.partition_point(|write| write.row < first_row)..]
+                .iter()
+                .take_while(|write| register_rows.contains(&write.row))
+            {
+                if let Some(&i) = register_indices.get(&write.address) {
+                    self.register_values[i] = write.value;
+                }
+            }
+        }
+        // Every execution of the executor ends with the same `FINAL_SINK_ROWS` rows, which
+        // set the machine registers to fixed values (the pc to the sink loop and the query
+        // arguments to zero). So after any chunk, they have the values of the last row of
+        // the full trace.
+        for (i, reg) in REGISTER_NAMES.iter().enumerate() {
+            self.register_values[REGISTER_MEMORY_NAMES.len() + i] =
+                *self.full_exec.trace[*reg].last().unwrap();
+        }
+        bootloader_inputs[(REGISTER_MEMORY_NAMES.len() + REGISTER_NAMES.len())
+            ..2 * (REGISTER_MEMORY_NAMES.len() + REGISTER_NAMES.len())]
+            .copy_from_slice(&self.register_values);
+
+        match self.options.resume_from.get(self.chunk_index) {
+            Some(resumed_inputs) if resumed_inputs != &bootloader_inputs => {
+                Err(ContinuationError::ResumeMismatch {
+                    chunk_index: self.chunk_index,
+                })
+            }
+            _ => Ok(bootloader_inputs),
+        }
+    }
+
+    /// Advances over the next chunk without executing it, see `Iterator::nth`.
+    /// Returns the inputs of the chunk.
+    fn skip_chunk(&mut self) -> Result<Vec<F>, ContinuationError<F>> {
+        let chunk_index = self.chunk_index;
+        let resumed = self.resumed_chunks.get(chunk_index).cloned();
+        let accessed_pages = match &resumed {
+            Some(layout) => layout.accessed_pages.clone(),
+            None => self.accessed_pages(self.proven_trace)?,
+        };
+        let bootloader_inputs = bootloader::create_input(
+            self.register_values.clone(),
+            &self.merkle_tree,
+            accessed_pages.iter().cloned(),
+        );
+        let layout = match resumed {
+            Some(layout) => layout,
+            None => self.chunk_layout(
+                chunk_index,
+                self.proven_trace,
+                accessed_pages,
+                &bootloader_inputs,
+            )?,
+        };
+        tracing::info!("Skipping chunk {}...", chunk_index);
+        let bootloader_inputs = self.fast_forward(&layout, bootloader_inputs)?;
+        self.proven_trace += layout.rows.new_rows;
+        self.chunk_index += 1;
+        self.done = layout.rows.is_last_chunk;
+        Ok(bootloader_inputs)
+    }
+
+    /// Computes the inputs of the next chunk, executing and validating it unless it is
+    /// resumed.
+    fn step(&mut self) -> Result<ChunkOutput<F>, ContinuationError<F>> {
+        let chunk_index = self.chunk_index;
+        let length = self.length;
+        let proven_trace = self.proven_trace;
+
+        // The span fields are recorded as soon as they are known, so that
+        // log processors can filter and time the individual chunks.
+        let chunk_span = tracing::info_span!(
+            "chunk",
+            chunk = chunk_index,
+            accessed_pages = tracing::field::Empty,
+            bootloader_rows = tracing::field::Empty,
+        )
+        .entered();
+
+        tracing::info!("\nRunning chunk {} for {} steps...", chunk_index, length);
+
+        let (bootloader_inputs, chunk_exec, accessed_pages, num_rows, start) = match self
+            .resumed_chunks
+            .get(chunk_index)
+            .cloned()
+        {
+            Some(layout) => {
+                tracing::info!("Fast-forwarding over resumed chunk {}...", chunk_index);
+                let bootloader_inputs = bootloader::create_input(
+                    self.register_values.clone(),
+                    &self.merkle_tree,
+                    layout.accessed_pages.iter().cloned(),
+                );
+                let bootloader_inputs = self.fast_forward(&layout, bootloader_inputs)?;
+                (
+                    bootloader_inputs,
+                    None,
+                    layout.accessed_pages,
+                    layout.num_rows,
+                    layout.bootloader_rows,
+                )
+            }
+            None => {
+                tracing::info!("Building bootloader inputs for chunk {}...", chunk_index);
+                let accessed_pages = self.accessed_pages(proven_trace)?;
+                let kept_pages = self.kept_pages(chunk_index);
+                let kept_memory = self.kept_memory(&kept_pages);
+
+                let shutdown_routine_rows = shutdown_routine_upper_bound(accessed_pages.len());
+                tracing::info!(
+                    "Estimating the shutdown routine to use {} rows.",
+                    shutdown_routine_rows
+                );
+                let mut num_rows = length - shutdown_routine_rows;
+
+                // Build the bootloader inputs for the current chunk.
+                // Note that while we do know the accessed pages, we don't yet know the hashes
+                // of those pages at the end of the execution, because that will depend on how
+                // long the bootloader runs.
+                // Similarly, we don't yet know the final register values.
+                // So, we do a bit of a hack: For now, we'll just pretend that the state does not change, i.e.:
+                // - The final register values are equal to the initial register values.
+                // - The updated page hashes are equal to the current page hashes.
+                // - The updated root hash is equal to the current root hash.
+                // After simulating the chunk execution, we'll replace those values with the actual values.
+                let mut bootloader_inputs = bootloader::create_input(
+                    std::mem::take(&mut self.register_values),
+                    &self.merkle_tree,
+                    accessed_pages.iter().cloned(),
+                );
+
+                tracing::info!("Bootloader inputs length: {}", bootloader_inputs.len());
+                tracing::info!(
+                    "Initial memory root hash: {}",
+                    render_memory_hash(
+                        &bootloader_inputs[MEMORY_HASH_START_INDEX..MEMORY_HASH_START_INDEX + 8]
+                    )
+                );
+
+                tracing::info!("Simulating chunk execution...");
+                let execute_chunk = |num_rows| {
+                    execute_chunk(
+                        &self.asm,
+                        &self.pil,
+                        self.fixed.clone(),
+                        self.pipeline,
+                        &bootloader_inputs,
+                        kept_memory.clone(),
+                        num_rows,
+                    )
+                };
+                let find_start = |chunk_exec: &Execution<F>| {
+                    find_pc(&chunk_exec.trace, bootloader_inputs[PC_INDEX], || {
+                        format!("trace of chunk {chunk_index}")
+                    })
+                };
+                let mut chunk_exec = execute_chunk(num_rows);
+                let mut start = find_start(&chunk_exec)?;
+                if let Some(boundaries) = self.options.prefer_boundaries {
+                    // Only chunks that are not the last one can end earlier.
+                    if chunk_exec.trace_len == num_rows {
+                        if let Some(rows) = rows_until_preferred_boundary(
+                            boundaries,
+                            self.options.boundary_slack,
+                            proven_trace,
+                            start,
+                            num_rows,
+                        ) {
+                            tracing::info!(
+                                "Ending the chunk at a preferred boundary after {rows} instead of {num_rows} rows."
+                            );
+                            num_rows = rows;
+                            chunk_exec = execute_chunk(num_rows);
+                            start = find_start(&chunk_exec)?;
+                        }
+                    }
+                }
+
+                self.register_values = update_bootloader_inputs(
+                    &mut bootloader_inputs,
+                    &mut self.merkle_tree,
+                    &accessed_pages,
+                    &kept_pages,
+                    &mut chunk_exec,
+                );
+                (
+                    bootloader_inputs,
+                    Some(chunk_exec),
+                    accessed_pages,
+                    num_rows,
+                    start,
+                )
+            }
+        };
+        let chunk_trace_len = chunk_exec.as_ref().map_or_else(
+            || self.resumed_chunks[chunk_index].trace_len,
+            |e| e.trace_len,
+        );
+        chunk_span.record("accessed_pages", accessed_pages.len());
+        chunk_span.record("bootloader_rows", start);
+        let options = &mut self.options;
+        let shutdown_routine_rows = shutdown_routine_upper_bound(accessed_pages.len());
+
+        tracing::info!(
+            "Initial memory root hash: {}",
+            render_memory_hash(
+                &bootloader_inputs[MEMORY_HASH_START_INDEX..MEMORY_HASH_START_INDEX + 8]
+            )
+        );
+        tracing::info!(
+            "Final memory root hash: {}",
+            render_memory_hash(
+                &bootloader_inputs[MEMORY_HASH_START_INDEX + 8..MEMORY_HASH_START_INDEX + 16]
+            )
+        );
+
+        if let Some(dir) = options.inputs_output_dir {
+            write_chunk_inputs(dir, chunk_index, &bootloader_inputs, chunk_trace_len as u64)
+                .map_err(|error| ContinuationError::WriteInputs { chunk_index, error })?;
+        }
+
+        tracing::info!("Chunk trace length: {}", chunk_trace_len);
+        tracing::info!("Bootloader used {} rows.", start);
+        tracing::info!(
+            "  => {} / {} ({}%) of rows are used for the actual computation!",
+            length - start - shutdown_routine_rows,
             length,
             (length - start - shutdown_routine_rows) * 100 / length
         );
-        for i in 0..(chunk_exec.trace_len - start) {
-            for &reg in ["main::pc", "main::query_arg_1", "main::query_arg_2"].iter() {
-                let chunk_i = i + start;
-                let full_i = i + proven_trace;
-                if chunk_exec.trace[reg][chunk_i] != full_exec.trace[reg][full_i] {
-                    log::error!("The Chunk trace differs from the full trace!");
-                    log::error!(
-                        "Started comparing from row {start} in the chunk to row {proven_trace} in the full trace; the difference is at offset {i}."
-                    );
-                    log::error!(
-                        "The PCs are {} and {}.",
-                        chunk_exec.trace["main::pc"][chunk_i],
-                        full_exec.trace["main::pc"][full_i]
-                    );
-                    log::error!(
-                        "The first difference is in register {}: {} != {} ",
-                        reg,
-                        chunk_exec.trace[reg][chunk_i],
-                        full_exec.trace[reg][full_i],
-                    );
-                    panic!();
+        let validated_rows = match options.validation_level {
+            ValidationLevel::None => None,
+            ValidationLevel::ChunkBoundaries => Some(start + 1),
+            ValidationLevel::Full => Some(chunk_trace_len),
+        };
+        match (&chunk_exec, validated_rows) {
+            (Some(chunk_exec), Some(validated_rows)) => {
+                tracing::info!("Validating chunk...");
+                validate_chunk(
+                    &chunk_exec.trace,
+                    &self.full_exec.trace,
+                    validated_rows,
+                    start,
+                    proven_trace,
+                    options.value_format,
+                    options.compare,
+                )
+                .map_err(|report| {
+                    if let Some(dir) = options.trace_dump_dir {
+                        let window = TraceWindow {
+                            chunk_start: start,
+                            full_start: proven_trace,
+                            len: report.first_offset + 1,
+                        };
+                        match write_trace_dump(
+                            dir,
+                            chunk_index,
+                            &chunk_exec.trace,
+                            &self.full_exec.trace,
+                            window,
+                            options.value_format,
+                        ) {
+                            Ok(()) => tracing::error!(
+                                "Wrote the traces around the difference to {}.",
+                                dir.display()
+                            ),
+                            Err(e) => tracing::error!("Failed to write the traces: {e}"),
+                        }
+                    }
+                    ContinuationError::TraceMismatch {
+                        chunk_index,
+                        report,
+                    }
+                })?;
+            }
+            (None, _) => tracing::info!("Skipping validation of resumed chunk."),
+            (Some(_), None) => {}
+        }
+
+        let ChunkRows {
+            new_rows,
+            is_last_chunk,
+        } = chunk_rows(chunk_trace_len, start, num_rows).ok_or(ContinuationError::NoProgress {
+            chunk_index,
+            chunk_trace_len,
+            bootloader_rows: start,
+            num_rows,
+        })?;
+        self.proven_trace += new_rows;
+        let stats = ChunkStats {
+            chunk_index,
+            accessed_pages: accessed_pages.into_iter().collect(),
+            num_rows: chunk_trace_len,
+            bootloader_rows: start,
+            real_rows: new_rows,
+            cumulative_proven: self.proven_trace,
+        };
+        if let Some(out) = options.stats_csv.as_mut() {
+            writeln!(out, "{}", stats.to_csv_line())
+                .and_then(|_| out.flush())
+                .map_err(ContinuationError::WriteStats)?;
+        }
+
+        if is_last_chunk {
+            // The last rows of the trace jump to the sink loop, so look at the
+            // last row that was actually executed. A resumed last chunk ends
+            // like the full trace.
+            let (trace, trace_len) = chunk_exec
+                .as_ref()
+                .map_or((&self.full_exec.trace, self.full_exec.trace_len), |e| {
+                    (&e.trace, e.trace_len)
+                });
+            if let Some(expected) = self.halt_pc {
+                let final_pc = trace_len
+                    .checked_sub(FINAL_SINK_ROWS + 1)
+                    .map(|row| trace["main::pc"][row].to_degree())
+                    .ok_or(ContinuationError::TraceTooShort { trace_len })?;
+                if final_pc != expected {
+                    return Err(ContinuationError::UnexpectedFinalPc { expected, final_pc });
                 }
+            } else {
+                tracing::warn!("Could not determine the halt pc, not checking the final pc.");
+            }
+        }
+
+        self.chunk_index += 1;
+        Ok(ChunkOutput {
+            bootloader_inputs,
+            stats,
+            is_last_chunk,
+        })
+    }
+}
+
+impl<F: FieldElement> Iterator for ContinuationDryRun<'_, '_, F> {
+    type Item = Result<(Vec<F>, u64), ContinuationError<F>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let output = self.step();
+        self.done = output.as_ref().map_or(true, |output| output.is_last_chunk);
+        Some(output.map(|output| (output.bootloader_inputs, output.stats.num_rows as u64)))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        for _ in 0..n {
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.skip_chunk() {
+                self.done = true;
+                return Some(Err(e));
             }
         }
+        self.next()
+    }
+}
+
+/// Runs the entire execution using the RISC-V executor. For each chunk, it collects:
+/// - The inputs to the bootloader, needed to restore the correct state.
+/// - The number of rows after which the prover should jump to the shutdown routine.
+///
+/// Panics if the dry run fails, see `rust_continuations_dry_run_with_options`
+/// for a version that returns an error instead.
+pub fn rust_continuations_dry_run<F: FieldElement>(
+    pipeline: &mut Pipeline<F>,
+    profiler_opt: Option<ProfilerOptions>,
+) -> DryRunResult<F> {
+    rust_continuations_dry_run_with_options(pipeline, profiler_opt, Default::default())
+        .unwrap_or_else(|e| panic!("Continuations dry run failed: {e}"))
+}
 
-        if chunk_exec.trace_len < num_rows {
-            log::info!("Done!");
+/// Same as `rust_continuations_dry_run`, but customizable via `options`.
+pub fn rust_continuations_dry_run_with_options<F: FieldElement>(
+    pipeline: &mut Pipeline<F>,
+    profiler_opt: Option<ProfilerOptions>,
+    options: DryRunOptions<'_, F>,
+) -> Result<DryRunResult<F>, ContinuationError<F>> {
+    let mut dry_run = ContinuationDryRun::with_options(pipeline, profiler_opt, options)?;
+    let full_trace_length = dry_run.full_exec.trace_len;
+
+    let instruction_histogram = dry_run.options.instruction_histogram_top_n.map(|top_n| {
+        let histogram = instruction_histogram(&dry_run.full_exec.trace);
+        tracing::info!("Most executed instructions:");
+        for (name, count) in histogram
+            .iter()
+            .sorted_by(|(n1, c1), (n2, c2)| c2.cmp(c1).then(n1.cmp(n2)))
+            .take(top_n)
+        {
+            tracing::info!("  {name}: {count}");
+        }
+        histogram
+    });
+
+    // All inputs for all chunks.
+    let mut bootloader_inputs_and_num_rows = vec![];
+
+    // Statistics for the summary at the end of the dry run.
+    let mut chunk_stats = vec![];
+    let mut total_bootloader_rows = 0;
+    let mut total_pages = 0;
+    let mut max_pages_in_a_chunk = 0;
+    let mut unchanged_registers = vec![];
+    // The register values at the end of the previous chunk.
+    let mut previous_register_values: Option<Vec<F>> = None;
+    let mut input_digests = vec![];
+    let mut transition_registers = vec![];
+
+    loop {
+        if dry_run
+            .options
+            .cancel
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+        {
+            tracing::info!("Dry run cancelled before chunk {}.", dry_run.chunk_index);
+            return Err(ContinuationError::Cancelled {
+                bootloader_inputs: bootloader_inputs_and_num_rows,
+            });
+        }
+
+        let ChunkOutput {
+            mut bootloader_inputs,
+            stats,
+            is_last_chunk,
+        } = dry_run.step()?;
+        let options = &dry_run.options;
+        let register_values = &dry_run.register_values;
+
+        if options.count_unchanged_registers {
+            if let Some(previous) = &previous_register_values {
+                unchanged_registers.push(
+                    previous
+                        .iter()
+                        .zip(register_values)
+                        .filter(|(a, b)| a == b)
+                        .count(),
+                );
+            }
+            previous_register_values = Some(register_values.clone());
+        }
+        if options.transition_registers_json.is_some() {
+            transition_registers.push(register_values.clone());
+        }
+
+        if options.compute_input_digests {
+            input_digests.push(bootloader_inputs_digest(&bootloader_inputs));
+        }
+        if options.discard_bootloader_inputs {
+            bootloader_inputs = vec![];
+        }
+        bootloader_inputs_and_num_rows.push((bootloader_inputs, stats.num_rows as u64));
+
+        total_bootloader_rows += stats.bootloader_rows;
+        total_pages += stats.accessed_pages.len();
+        max_pages_in_a_chunk = max_pages_in_a_chunk.max(stats.accessed_pages.len());
+        let new_rows = stats.real_rows;
+        chunk_stats.push(stats);
+
+        if is_last_chunk {
+            tracing::info!("Done!");
             break;
         }
-        assert_eq!(chunk_exec.trace_len, num_rows);
+        tracing::info!("Proved {} rows.", new_rows);
+    }
 
-        // Minus one, because the last row will have to be repeated in the next chunk.
-        let new_rows = num_rows - start - 1;
-        proven_trace += new_rows;
-        log::info!("Proved {} rows.", new_rows);
+    // Every row of the full trace should be proven by exactly one chunk.
+    // Anything else is a bug in the chunking arithmetic above.
+    let proven_trace = dry_run.proven_trace;
+    if proven_trace != full_trace_length {
+        return Err(ContinuationError::CoverageMismatch {
+            proven_rows: proven_trace,
+            full_trace_length,
+        });
+    }
 
-        chunk_index += 1;
+    if let Some(path) = dry_run.options.transition_registers_json {
+        write_transition_registers(path, &transition_registers)
+            .map_err(ContinuationError::WriteTransitionRegisters)?;
     }
-    DryRunResult {
+
+    let num_chunks = bootloader_inputs_and_num_rows.len();
+
+    let summary = ContinuationSummary {
+        num_chunks,
+        total_rows_proven: proven_trace,
+        total_bootloader_rows,
+        avg_pages_per_chunk: total_pages as f64 / num_chunks as f64,
+        max_pages_in_a_chunk,
+    };
+    tracing::info!("Dry run summary: {summary}");
+
+    Ok(DryRunResult {
         bootloader_inputs: bootloader_inputs_and_num_rows,
         trace_len: full_trace_length,
+        summary,
+        instruction_histogram,
+        final_memory_root: *dry_run.merkle_tree.root_hash(),
+        full_trace: dry_run
+            .options
+            .keep_full_trace
+            .then_some(dry_run.full_exec.trace),
+        chunk_stats,
+        unchanged_registers: dry_run
+            .options
+            .count_unchanged_registers
+            .then_some(unchanged_registers),
+        input_digests: dry_run
+            .options
+            .compute_input_digests
+            .then_some(input_digests),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use powdr_number::GoldilocksField;
+
+    use crate::{compile_rust_crate_to_riscv, CompilerOptions};
+
+    use super::*;
+
+    /// Returns a pipeline for the `few_chunks` test program, compiled with a small
+    /// max degree so that it is split into a few chunks.
+    fn few_chunks_pipeline() -> Pipeline<GoldilocksField> {
+        let temp_dir = mktemp::Temp::new_dir().unwrap();
+        let executable =
+            compile_rust_crate_to_riscv("tests/riscv_data/few_chunks/Cargo.toml", &temp_dir, None);
+        let options = CompilerOptions::new_gl()
+            .with_continuations()
+            .with_max_degree_log(14);
+        Pipeline::default()
+            .from_asm_string(
+                crate::elf::translate(&executable, options),
+                Some(PathBuf::from("few_chunks")),
+            )
+            .with_prover_inputs(Default::default())
+    }
+
+    #[test]
+    fn fast_forward_matches_step() {
+        let mut pipeline = few_chunks_pipeline();
+        let expected = ContinuationDryRun::new(&mut pipeline, None)
+            .unwrap()
+            .map(|chunk| chunk.map(|(inputs, _)| inputs))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(expected.len() > 2);
+
+        // Fast-forwarding over a chunk computes the inputs of executing it.
+        let mut dry_run = ContinuationDryRun::new(&mut pipeline, None).unwrap();
+        for (chunk_index, inputs) in expected.iter().enumerate() {
+            assert!(!dry_run.done);
+            assert_eq!(
+                &dry_run.skip_chunk().unwrap(),
+                inputs,
+                "chunk {chunk_index}"
+            );
+        }
+        assert!(dry_run.done);
+
+        // The state after fast-forwarding is the one after executing, so the chunks can
+        // be mixed.
+        let mut dry_run = ContinuationDryRun::new(&mut pipeline, None).unwrap();
+        for (chunk_index, inputs) in expected.iter().enumerate() {
+            let computed = match chunk_index % 2 {
+                0 => dry_run.skip_chunk().unwrap(),
+                _ => dry_run.step().unwrap().bootloader_inputs,
+            };
+            assert_eq!(&computed, inputs, "chunk {chunk_index}");
+        }
     }
 }
@@ -176,7 +176,18 @@ where
         inputs.extend(self.merkle_tree_root_hash.iter().flat_map(|v| split_fe(*v)));
 
         inputs.push((self.pages.len() as i64).into());
+        let mut previous_page_idx = None;
         for page in self.pages {
+            // The pages are collected in a set today, but a page appearing twice
+            // would silently corrupt the inputs, so we check it here.
+            if let Some(previous) = previous_page_idx {
+                assert!(
+                    page.page_idx > previous,
+                    "Page indices need to be strictly increasing, but page {} follows page {previous}",
+                    page.page_idx
+                );
+            }
+            previous_page_idx = Some(page.page_idx);
             inputs.push(page.page_idx.into());
             inputs.extend(page.data);
             inputs.extend(page.hash.iter().flat_map(|v| split_fe(*v)));
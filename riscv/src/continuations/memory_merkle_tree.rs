@@ -7,6 +7,7 @@ use super::bootloader::{
     BYTES_PER_WORD, N_LEAVES_LOG, WORDS_PER_PAGE as WORDS_PER_PAGE_BOOTLOADER,
 };
 
+use itertools::Itertools;
 use powdr_number::{FieldElement, GoldilocksField};
 use powdr_riscv_executor::poseidon_gl::poseidon_gl;
 
@@ -35,15 +36,29 @@ pub struct MerkleTree<
     data: HashMap<usize, [T; WORDS_PER_PAGE]>,
 
     /// The default hash for each level of the tree, when all leaves below are
-    /// zeroed.
+    /// zeroed. Merkle proofs of untouched subtrees refer to these hashes.
     default_hashes: [[T; 4]; N_LEVELS],
 
     /// A zeroed page, used as a default value for missing pages.
+    /// It is shared by all untouched pages, so that `get` never needs to
+    /// allocate page data.
     zero_page: [T; WORDS_PER_PAGE],
+
+    /// If set, the maximum number of distinct pages that can be stored.
+    page_limit: Option<usize>,
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of hashes computed by `hash_cap0` in this thread, so that tests can
+    /// check how many hashes an operation needs.
+    static HASH_COUNT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
 }
 
 /// Computes the Poseidon hash of two 4-field-element inputs, using a capacity of 0.
 fn hash_cap0<T: FieldElement>(data1: &[T; 4], data2: &[T; 4]) -> [T; 4] {
+    #[cfg(test)]
+    HASH_COUNT.with(|count| count.set(count.get() + 1));
     let mut buffer = [T::zero(); 12];
     buffer[..4].copy_from_slice(data1);
     buffer[4..8].copy_from_slice(data2);
@@ -61,6 +76,18 @@ fn level_up(ordered_indices: &mut Vec<usize>) {
     ordered_indices.dedup();
 }
 
+/// The hashes needed to recompute the root hash of a Merkle tree before and after
+/// a set of pages is written, see `MerkleTree::transition_proof`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransitionProof<T> {
+    /// The hashes of the written pages before the writes, sorted by page index.
+    pub old_page_hashes: Vec<[T; 4]>,
+    /// The hashes of all nodes that are siblings of a node on the path from a written
+    /// page to the root, but are not on such a path themselves. They are not changed
+    /// by the writes. Ordered level by level from the leaves up, and by index within a level.
+    pub siblings: Vec<[T; 4]>,
+}
+
 impl<T: FieldElement, const N_LEVELS: usize, const WORDS_PER_PAGE: usize>
     MerkleTree<T, N_LEVELS, WORDS_PER_PAGE>
 {
@@ -80,6 +107,17 @@ impl<T: FieldElement, const N_LEVELS: usize, const WORDS_PER_PAGE: usize>
             data: HashMap::new(),
             default_hashes: Self::default_hashes_per_level(),
             zero_page: [T::zero(); WORDS_PER_PAGE],
+            page_limit: None,
+        }
+    }
+
+    /// Build a new Merkle tree starting from an all-zero memory, that panics
+    /// if more than `limit` distinct pages are updated. Useful in tests to
+    /// detect an unexpected number of accessed pages.
+    pub fn with_page_limit(limit: usize) -> Self {
+        Self {
+            page_limit: Some(limit),
+            ..Self::new()
         }
     }
 
@@ -114,6 +152,10 @@ impl<T: FieldElement, const N_LEVELS: usize, const WORDS_PER_PAGE: usize>
 
     /// Applies updates, given an iterator of (memory address, value) pairs.
     /// Memory addresses are assumed to be word-aligned.
+    ///
+    /// Updates are grouped by page first, so the hash of each updated page and
+    /// of each inner node is only computed once, no matter how many updates
+    /// hit the same page.
     pub fn update(&mut self, updates: impl Iterator<Item = (u32, u32)>) {
         let mut updated_indices = Vec::new();
 
@@ -142,6 +184,12 @@ impl<T: FieldElement, const N_LEVELS: usize, const WORDS_PER_PAGE: usize>
     }
 
     fn update_page_impl(&mut self, page_index: usize, updates: &[(usize, u32)]) {
+        if let Some(limit) = self.page_limit {
+            assert!(
+                self.data.len() < limit || self.data.contains_key(&page_index),
+                "Inserting page {page_index} exceeds the limit of {limit} distinct pages"
+            );
+        }
         let page = &mut self
             .data
             .entry(page_index)
@@ -187,6 +235,23 @@ impl<T: FieldElement, const N_LEVELS: usize, const WORDS_PER_PAGE: usize>
         self.get_hash(0, 0)
     }
 
+    /// Returns the depth of the tree, i.e. the number of levels below the root
+    /// and the length of the Merkle proofs.
+    pub fn depth(&self) -> usize {
+        N_LEVELS - 1
+    }
+
+    /// Returns the page index and the leaf hash of all pages that have been
+    /// updated, sorted by page index. All other leaves have the hash of a zeroed page.
+    /// Together with `depth`, this is enough to recompute the root hash.
+    pub fn leaf_commitments(&self) -> Vec<(usize, Vec<T>)> {
+        self.data
+            .keys()
+            .map(|&page_index| (page_index, self.get_hash(N_LEVELS - 1, page_index).to_vec()))
+            .sorted_by_key(|(page_index, _)| *page_index)
+            .collect()
+    }
+
     /// Returns the data and Merkle proof for a given page.
     pub fn get(&self, page_index: usize) -> (&[T; WORDS_PER_PAGE], &[T; 4], Vec<&[T; 4]>) {
         let mut proof = vec![];
@@ -203,6 +268,128 @@ impl<T: FieldElement, const N_LEVELS: usize, const WORDS_PER_PAGE: usize>
         (page_data, page_hash, proof)
     }
 
+    /// Returns the proof that writing the given pages, given as (page index, page data)
+    /// pairs, changes the current root hash into the root hash after the writes.
+    /// The tree itself is not modified. See `verify_transition` for the verifier.
+    pub fn transition_proof(&self, writes: &[(usize, Vec<T>)]) -> TransitionProof<T> {
+        let pages = writes
+            .iter()
+            .map(|(page_index, data)| {
+                assert_eq!(
+                    data.len(),
+                    WORDS_PER_PAGE,
+                    "Invalid data for page {page_index}"
+                );
+                *page_index
+            })
+            .sorted()
+            .collect::<Vec<_>>();
+        assert!(
+            pages.iter().tuple_windows().all(|(a, b)| a < b),
+            "Pages are written more than once"
+        );
+        let old_page_hashes = pages
+            .iter()
+            .map(|&page_index| *self.get_hash(N_LEVELS - 1, page_index))
+            .collect::<Vec<_>>();
+        let mut siblings = vec![];
+        if !pages.is_empty() {
+            let root = Self::root_from_nodes(
+                pages
+                    .iter()
+                    .copied()
+                    .zip(old_page_hashes.iter().copied())
+                    .collect(),
+                |level, index| {
+                    let hash = *self.get_hash(level, index);
+                    siblings.push(hash);
+                    Some(hash)
+                },
+            );
+            assert_eq!(root.as_ref(), Some(self.root_hash()));
+        }
+        TransitionProof {
+            old_page_hashes,
+            siblings,
+        }
+    }
+
+    /// Verifies a proof produced by `transition_proof`, i.e. checks that writing the
+    /// given pages changes the root hash `old_root` into `new_root`.
+    pub fn verify_transition(
+        old_root: &[T; 4],
+        new_root: &[T; 4],
+        writes: &[(usize, Vec<T>)],
+        proof: &TransitionProof<T>,
+    ) -> bool {
+        if writes.len() != proof.old_page_hashes.len()
+            || writes.iter().any(|(page_index, data)| {
+                *page_index >= 1 << (N_LEVELS - 1) || data.len() != WORDS_PER_PAGE
+            })
+        {
+            return false;
+        }
+        let new_pages = writes
+            .iter()
+            .map(|(page_index, data)| {
+                (
+                    *page_index,
+                    Self::hash_page(data.as_slice().try_into().unwrap()),
+                )
+            })
+            .sorted_by_key(|(page_index, _)| *page_index)
+            .collect::<Vec<_>>();
+        if !new_pages.iter().tuple_windows().all(|(a, b)| a.0 < b.0) {
+            return false;
+        }
+        if new_pages.is_empty() {
+            return old_root == new_root && proof.siblings.is_empty();
+        }
+        let old_pages: Vec<_> = new_pages
+            .iter()
+            .map(|(page_index, _)| *page_index)
+            .zip(proof.old_page_hashes.iter().copied())
+            .collect();
+        // The same siblings are used for both roots, and all of them have to be used.
+        let root_with_siblings = |nodes: Vec<(usize, [T; 4])>| {
+            let mut siblings = proof.siblings.iter();
+            let root = Self::root_from_nodes(nodes, |_, _| siblings.next().copied());
+            root.filter(|_| siblings.next().is_none())
+        };
+        root_with_siblings(old_pages).as_ref() == Some(old_root)
+            && root_with_siblings(new_pages).as_ref() == Some(new_root)
+    }
+
+    /// Computes the root hash from the hashes of some distinct leaves, sorted by page index.
+    /// The hashes of the other nodes needed along the way are requested from `sibling`,
+    /// given their level and index, in the order documented in `TransitionProof::siblings`.
+    fn root_from_nodes(
+        mut nodes: Vec<(usize, [T; 4])>,
+        mut sibling: impl FnMut(usize, usize) -> Option<[T; 4]>,
+    ) -> Option<[T; 4]> {
+        for level in (1..N_LEVELS).rev() {
+            let mut parents = vec![];
+            let mut nodes_in_level = nodes.into_iter().peekable();
+            while let Some((index, hash)) = nodes_in_level.next() {
+                let parent = if index % 2 == 0 {
+                    let right = match nodes_in_level.next_if(|(i, _)| *i == index + 1) {
+                        Some((_, right)) => right,
+                        None => sibling(level, index + 1)?,
+                    };
+                    hash_cap0(&hash, &right)
+                } else {
+                    hash_cap0(&sibling(level, index - 1)?, &hash)
+                };
+                parents.push((index >> 1, parent));
+            }
+            nodes = parents;
+        }
+        match nodes.as_slice() {
+            [(0, root)] => Some(*root),
+            _ => None,
+        }
+    }
+
     /// Yields (level, index) pairs for the path from the given page to the root.
     fn iter_path(&self, page_index: usize) -> impl Iterator<Item = (usize, usize)> {
         (0..N_LEVELS).rev().map(move |level| {
@@ -261,6 +448,13 @@ mod test {
         hash_cap0(&hash_1_0, &hash_1_1)
     }
 
+    /// Returns the number of hashes computed by `f`.
+    fn count_hashes(f: impl FnOnce()) -> usize {
+        let before = HASH_COUNT.with(|count| count.get());
+        f();
+        HASH_COUNT.with(|count| count.get()) - before
+    }
+
     #[test]
     fn update() {
         let mut tree = MerkleTree::<GoldilocksField, 3, 8>::new();
@@ -311,6 +505,170 @@ mod test {
         assert_eq!(tree.root_hash(), &expected_root_hash);
     }
 
+    #[test]
+    fn coalesced_updates() {
+        let updates = (0..64u32).map(|i| ((i % 8) * 4, i)).collect::<Vec<_>>();
+
+        let mut coalesced = MerkleTree::<GoldilocksField, 3, 8>::new();
+        let coalesced_hashes = count_hashes(|| coalesced.update(updates.iter().cloned()));
+
+        let mut one_by_one = MerkleTree::<GoldilocksField, 3, 8>::new();
+        let one_by_one_hashes = count_hashes(|| {
+            for update in &updates {
+                one_by_one.update(std::iter::once(*update));
+            }
+        });
+
+        // Hashing a page of 8 words takes 2 hashes, plus one per inner node on its path.
+        assert_eq!(coalesced_hashes, 4);
+        assert_eq!(one_by_one_hashes, updates.len() * 4);
+
+        // Only the last update of each word is visible.
+        let expected_page: [u64; 8] = std::array::from_fn(|i| 56 + i as u64);
+        let mut data = [[0; 8]; 4];
+        data[0] = expected_page;
+        assert_eq!(coalesced.root_hash(), one_by_one.root_hash());
+        assert_eq!(coalesced.root_hash(), &root_hash::<GoldilocksField>(&data));
+    }
+
+    #[test]
+    fn leaf_commitments() {
+        let mut tree = MerkleTree::<GoldilocksField, 3, 8>::new();
+        tree.update([((3 * 8 + 6) * 4, 4), (4 * 4, 1)].into_iter());
+        assert_eq!(tree.depth(), 2);
+
+        let leaves = tree.leaf_commitments();
+        let mut data = [[0; 8]; 4];
+        data[0][4] = 1;
+        data[3][6] = 4;
+        assert_eq!(
+            leaves,
+            vec![
+                (0, hash_page(&data[0]).to_vec()),
+                (3, hash_page(&data[3]).to_vec())
+            ]
+        );
+
+        // Recompute the root from the leaves.
+        let mut level: Vec<[GoldilocksField; 4]> = (0..1 << tree.depth())
+            .map(|i| {
+                leaves
+                    .iter()
+                    .find(|(page_index, _)| *page_index == i)
+                    .map(|(_, hash)| hash.clone().try_into().unwrap())
+                    .unwrap_or_else(|| hash_page(&[0; 8]))
+            })
+            .collect();
+        while level.len() > 1 {
+            level = level
+                .chunks_exact(2)
+                .map(|pair| hash_cap0(&pair[0], &pair[1]))
+                .collect();
+        }
+        assert_eq!(&level[0], tree.root_hash());
+    }
+
+    #[test]
+    fn page_limit() {
+        let mut tree = MerkleTree::<GoldilocksField, 3, 8>::with_page_limit(2);
+        tree.update([(4 * 4, 1), ((8 + 3) * 4, 2)].into_iter());
+        // Updating an existing page does not count towards the limit.
+        tree.update([(4 * 3, 5)].into_iter());
+    }
+
+    #[test]
+    #[should_panic = "exceeds the limit of 2 distinct pages"]
+    fn page_limit_exceeded() {
+        let mut tree = MerkleTree::<GoldilocksField, 3, 8>::with_page_limit(2);
+        tree.update([(4 * 4, 1), ((8 + 3) * 4, 2)].into_iter());
+        tree.update([((2 * 8 + 7) * 4, 3)].into_iter());
+    }
+
+    #[test]
+    fn get_untouched_page() {
+        let mut tree = MerkleTree::<GoldilocksField, 3, 8>::new();
+        tree.update([(4 * 4, 1)].into_iter());
+
+        // Uninitialized memory is all zeros, as in the PIL.
+        let (page, page_hash, proof) = tree.get(3);
+        assert_eq!(page, &[GoldilocksField::from(0); 8]);
+        assert_eq!(page_hash, &hash_page(&[0; 8]));
+
+        // All untouched pages and subtrees use the cached defaults.
+        assert!(std::ptr::eq(page, tree.get(2).0));
+        assert!(std::ptr::eq(proof[0], page_hash));
+
+        // Verify Merkle proof
+        assert_eq!(proof.len(), 2);
+        let computed_hash = hash_cap0(proof[0], page_hash);
+        let computed_hash = hash_cap0(proof[1], &computed_hash);
+        assert_eq!(computed_hash, *tree.root_hash());
+    }
+
+    #[test]
+    fn transition_proof() {
+        type Tree = MerkleTree<GoldilocksField, 3, 8>;
+        let g = GoldilocksField::from;
+        let mut tree = Tree::new();
+        tree.update([(4 * 4, 1), ((3 * 8 + 6) * 4, 4)].into_iter());
+        let old_root = *tree.root_hash();
+
+        let writes = vec![
+            (3, (0..8).map(|i| g(i + 10)).collect::<Vec<_>>()),
+            (1, (0..8).map(|i| g(i + 20)).collect::<Vec<_>>()),
+        ];
+        let proof = tree.transition_proof(&writes);
+        // Pages 0 and 2 are the only siblings needed, the nodes above are recomputed.
+        assert_eq!(proof.old_page_hashes.len(), 2);
+        assert_eq!(proof.siblings.len(), 2);
+
+        for (page_index, data) in &writes {
+            let updates = (0..8)
+                .map(|i| (i, data[i].to_degree() as u32))
+                .collect_vec();
+            tree.update_page(*page_index, &updates);
+        }
+        let new_root = *tree.root_hash();
+        assert!(Tree::verify_transition(
+            &old_root, &new_root, &writes, &proof
+        ));
+
+        // Wrong roots, writes or proofs are rejected.
+        assert!(!Tree::verify_transition(
+            &old_root, &old_root, &writes, &proof
+        ));
+        assert!(!Tree::verify_transition(
+            &new_root, &new_root, &writes, &proof
+        ));
+        let mut wrong_writes = writes.clone();
+        wrong_writes[0].1[0] = g(0);
+        assert!(!Tree::verify_transition(
+            &old_root,
+            &new_root,
+            &wrong_writes,
+            &proof
+        ));
+        assert!(!Tree::verify_transition(
+            &old_root,
+            &new_root,
+            &writes[..1],
+            &proof
+        ));
+        let mut wrong_proof = proof.clone();
+        wrong_proof.siblings.pop();
+        assert!(!Tree::verify_transition(
+            &old_root,
+            &new_root,
+            &writes,
+            &wrong_proof
+        ));
+
+        // Writing nothing does not change the root.
+        let proof = tree.transition_proof(&[]);
+        assert!(Tree::verify_transition(&new_root, &new_root, &[], &proof));
+        assert!(!Tree::verify_transition(&old_root, &new_root, &[], &proof));
+    }
+
     #[test]
     fn get() {
         let g = GoldilocksField::from;
@@ -6,7 +6,9 @@ use itertools::Itertools;
 
 use crate::code_gen::Register;
 
-use crate::runtime::{parse_instruction_declaration, SubMachine, SyscallImpl, EXTRA_REG_PREFIX};
+use crate::runtime::{
+    parse_instruction_declaration, syscall_handler_label, SubMachine, SyscallImpl, EXTRA_REG_PREFIX,
+};
 use crate::RuntimeLibs;
 
 /// RISCV powdr assembly runtime.
@@ -417,17 +419,21 @@ impl Runtime {
 
         let jump_table = self.syscalls.values().map(|s| {
             format!(
-                "branch_if_diff_equal 5, 0, {}, __ecall_handler_{};",
-                s.syscall as u8, s.syscall
+                "branch_if_diff_equal 5, 0, {}, {};",
+                s.syscall as u8,
+                syscall_handler_label(s.syscall)
             )
         });
 
         let invalid_handler = ["__invalid_syscall:".to_string(), "fail;".to_string()].into_iter();
 
-        let handlers = self.syscalls.iter().flat_map(|(syscall, implementation)| {
-            std::iter::once(format!("__ecall_handler_{syscall}:"))
-                .chain(implementation.statements.iter().cloned())
-                .chain([format!("jump_dyn 1, {};", Register::from("tmp1").addr())])
+        let handlers = self.syscalls.values().flat_map(|implementation| {
+            std::iter::once(format!(
+                "{}:",
+                syscall_handler_label(implementation.syscall)
+            ))
+            .chain(implementation.statements.iter().cloned())
+            .chain([format!("jump_dyn 1, {};", Register::from("tmp1").addr())])
         });
 
         ecall
@@ -5,6 +5,11 @@ use powdr_riscv_syscalls::Syscall;
 
 pub static EXTRA_REG_PREFIX: &str = "xtra";
 
+/// Returns the label of the handler of `syscall` in the ecall handler.
+pub fn syscall_handler_label(syscall: Syscall) -> String {
+    format!("__ecall_handler_{syscall}")
+}
+
 lazy_static::lazy_static! {
     static ref INSTRUCTION_DECLARATION_PARSER: powdr_parser::powdr::InstructionDeclarationParser
         = powdr_parser::powdr::InstructionDeclarationParser::new();
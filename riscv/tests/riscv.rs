@@ -7,12 +7,22 @@ use powdr_pipeline::{
     test_util::{run_pilcom_with_backend_variant, BackendVariant},
     Pipeline,
 };
-use powdr_riscv_executor::ProfilerOptions;
-use std::path::{Path, PathBuf};
+use powdr_riscv_executor::{ProfilerOptions, FINAL_SINK_ROWS};
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
 use test_log::test;
 
 use powdr_riscv::{
-    continuations::{rust_continuations, rust_continuations_dry_run},
+    continuations::{
+        assert_same_memory_root,
+        bootloader::{shutdown_routine_upper_bound, MEMORY_HASH_START_INDEX},
+        chunk_rows, group_chunks, prove_single_chunk, register_state_at, rust_continuations,
+        rust_continuations_batched, rust_continuations_dry_run,
+        rust_continuations_dry_run_with_options, write_transition_registers, ChunkRows, ChunkStats,
+        ContinuationDryRun, ContinuationError, ContinuationSession, DryRunOptions,
+    },
     CompilerOptions, RuntimeLibs,
 };
 
@@ -47,7 +57,7 @@ fn run_continuations_test(case: &str, powdr_asm: String) {
         Ok(())
     };
     let bootloader_inputs = rust_continuations_dry_run(&mut pipeline, Default::default());
-    rust_continuations(&mut pipeline, pipeline_callback, bootloader_inputs).unwrap();
+    rust_continuations(&mut pipeline, pipeline_callback, bootloader_inputs, None).unwrap();
 }
 
 /*
@@ -535,26 +545,110 @@ fn features_with_options<T: FieldElement>(options: CompilerOptions) {
     );
 }
 
-#[test]
-#[ignore = "Too slow"]
-fn many_chunks_dry() {
-    // Compiles and runs the many_chunks example with continuations, just computing
-    // and validating the bootloader inputs.
-    // Doesn't do a full witness generation, verification, or proving.
-    let case = "many_chunks";
+/// Compiles the given test crate with continuations and returns a pipeline for it,
+/// writing to `output_dir` if given.
+fn continuations_pipeline(case: &str, output_dir: Option<&Path>) -> Pipeline<GoldilocksField> {
+    continuations_pipeline_with_options(case, output_dir, CompilerOptions::new_gl())
+}
+
+/// Returns a pipeline for the `few_chunks` test crate, compiled with a small max
+/// degree so that the dry run quickly splits it into a few chunks.
+fn few_chunks_pipeline() -> Pipeline<GoldilocksField> {
+    continuations_pipeline_with_options(
+        "few_chunks",
+        None,
+        CompilerOptions::new_gl().with_max_degree_log(14),
+    )
+}
+
+fn continuations_pipeline_with_options(
+    case: &str,
+    output_dir: Option<&Path>,
+    options: CompilerOptions,
+) -> Pipeline<GoldilocksField> {
     let temp_dir = Temp::new_dir().unwrap();
     let executable = powdr_riscv::compile_rust_crate_to_riscv(
         &format!("tests/riscv_data/{case}/Cargo.toml"),
         &temp_dir,
         None,
     );
-    let powdr_asm =
-        powdr_riscv::elf::translate(&executable, CompilerOptions::new_gl().with_continuations());
+    let powdr_asm = powdr_riscv::elf::translate(&executable, options.with_continuations());
 
-    let mut pipeline = Pipeline::default()
+    let pipeline = Pipeline::default()
         .from_asm_string(powdr_asm, Some(PathBuf::from(case)))
         .with_prover_inputs(Default::default());
-    rust_continuations_dry_run::<GoldilocksField>(&mut pipeline, Default::default());
+    match output_dir {
+        Some(dir) => pipeline.with_output(dir.to_path_buf(), false),
+        None => pipeline,
+    }
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn many_chunks_dry() {
+    // Compiles and runs the many_chunks example with continuations, just computing
+    // and validating the bootloader inputs.
+    // Doesn't do a full witness generation, verification, or proving.
+    let mut pipeline = continuations_pipeline("many_chunks", None);
+    rust_continuations_dry_run(&mut pipeline, Default::default());
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn many_chunks_resume() {
+    let mut pipeline = continuations_pipeline("many_chunks", None);
+    let fresh = rust_continuations_dry_run(&mut pipeline, Default::default());
+    let num_chunks = fresh.bootloader_inputs.len();
+    assert!(num_chunks > 2);
+    let inputs = |n: usize| -> Vec<Vec<GoldilocksField>> {
+        fresh.bootloader_inputs[..n]
+            .iter()
+            .map(|(inputs, _)| inputs.clone())
+            .collect()
+    };
+
+    // Resuming from the first chunks produces the same result as a fresh run.
+    let options = DryRunOptions {
+        resume_from: inputs(2),
+        ..Default::default()
+    };
+    let resumed = rust_continuations_dry_run_with_options(&mut pipeline, None, options).unwrap();
+    assert_eq!(resumed.bootloader_inputs, fresh.bootloader_inputs);
+    assert_eq!(resumed.final_memory_root, fresh.final_memory_root);
+
+    // Resuming from all chunks does not execute any chunk.
+    let options = DryRunOptions {
+        resume_from: inputs(num_chunks),
+        ..Default::default()
+    };
+    let resumed = rust_continuations_dry_run_with_options(&mut pipeline, None, options).unwrap();
+    assert_eq!(resumed.bootloader_inputs, fresh.bootloader_inputs);
+
+    // A resumed chunk whose inputs differ from a fresh run is rejected.
+    let mut resume_from = inputs(2);
+    resume_from[1][MEMORY_HASH_START_INDEX + 8] += GoldilocksField::from(1);
+    let options = DryRunOptions {
+        resume_from,
+        ..Default::default()
+    };
+    assert!(matches!(
+        rust_continuations_dry_run_with_options(&mut pipeline, None, options),
+        Err(ContinuationError::ResumeMismatch { chunk_index: 1 })
+    ));
+
+    // More resumed chunks than the execution has are rejected.
+    let mut resume_from = inputs(num_chunks);
+    resume_from.push(resume_from[0].clone());
+    let options = DryRunOptions {
+        resume_from,
+        ..Default::default()
+    };
+    let Err(ContinuationError::TooManyResumedChunks { provided, actual }) =
+        rust_continuations_dry_run_with_options(&mut pipeline, None, options)
+    else {
+        panic!("expected too many resumed chunks to be rejected");
+    };
+    assert_eq!((provided, actual), (num_chunks + 1, num_chunks));
 }
 
 use serde::{Deserialize, Serialize};
@@ -614,6 +708,344 @@ fn many_chunks_memory() {
     test_continuations("many_chunks_memory")
 }
 
+#[test]
+fn chunk_groups() {
+    assert!(group_chunks(&[], 10).is_empty());
+    assert_eq!(
+        group_chunks(&[4, 4, 4, 12, 1, 9], 10),
+        vec![0..2, 2..3, 3..4, 4..6]
+    );
+    assert_eq!(group_chunks(&[10, 10], 10), vec![0..1, 1..2]);
+    assert_eq!(group_chunks(&[1, 2, 3], 100), vec![0..3]);
+}
+
+#[test]
+fn chunk_row_accounting() {
+    assert_eq!(
+        chunk_rows(100, 10, 100),
+        Some(ChunkRows {
+            new_rows: 89,
+            is_last_chunk: false
+        })
+    );
+    assert_eq!(
+        chunk_rows(50, 10, 100),
+        Some(ChunkRows {
+            new_rows: 40,
+            is_last_chunk: true
+        })
+    );
+    // The bootloader uses all rows but the last one, no progress.
+    assert_eq!(chunk_rows(100, 99, 100), None);
+    assert_eq!(chunk_rows(50, 60, 100), None);
+    assert_eq!(chunk_rows(101, 10, 100), None);
+}
+
+#[test]
+fn chunk_groups_by_total_rows() {
+    let stats = |chunk_index, num_pages: u32, num_rows| ChunkStats {
+        chunk_index,
+        accessed_pages: (0..num_pages).collect(),
+        num_rows,
+        bootloader_rows: 0,
+        real_rows: 0,
+        cumulative_proven: 0,
+    };
+    let chunks = [stats(0, 1, 100), stats(1, 0, 100), stats(2, 0, 50)];
+    for chunk in &chunks {
+        assert_eq!(
+            chunk.total_rows(),
+            chunk.num_rows + shutdown_routine_upper_bound(chunk.accessed_pages.len())
+        );
+    }
+    let total_rows = chunks
+        .iter()
+        .map(|chunk| chunk.total_rows() as u64)
+        .collect::<Vec<_>>();
+    let max_rows = total_rows[1] + total_rows[2];
+    // The shutdown routine of the first chunk pages out a page, so it does not fit
+    // into a group with the second chunk, even though their traces would.
+    assert_eq!(group_chunks(&total_rows, max_rows), vec![0..1, 1..3]);
+    let trace_rows = chunks
+        .iter()
+        .map(|chunk| chunk.num_rows as u64)
+        .collect::<Vec<_>>();
+    assert_eq!(group_chunks(&trace_rows, max_rows), vec![0..2, 2..3]);
+}
+
+#[test]
+fn register_state_at_row() {
+    let trace: HashMap<String, Vec<GoldilocksField>> = [
+        ("main::pc".to_string(), vec![0u64, 1, 2]),
+        ("main::X".to_string(), vec![7, 8, 9]),
+    ]
+    .into_iter()
+    .map(|(name, values)| {
+        (
+            name,
+            values.into_iter().map(GoldilocksField::from).collect(),
+        )
+    })
+    .collect();
+    let state = register_state_at(&trace, 1).unwrap();
+    assert_eq!(state.len(), 2);
+    assert_eq!(state["main::pc"], GoldilocksField::from(1));
+    assert_eq!(state["main::X"], GoldilocksField::from(8));
+    assert_eq!(register_state_at(&trace, 3), None);
+}
+
+#[test]
+fn transition_registers_json() {
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let path = tmp_dir.join("registers.json");
+    let values = (0..2u64)
+        .map(|chunk| {
+            (0..39u64)
+                .map(|i| GoldilocksField::from(chunk * 100 + i))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    write_transition_registers(&path, &values).unwrap();
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let chunks = json.as_array().unwrap();
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[1]["chunk"], 1);
+    assert_eq!(chunks[1]["registers"]["main::x1"], "100");
+    assert_eq!(chunks[1]["registers"]["main::pc"], "138");
+    assert_eq!(chunks[0]["registers"].as_object().unwrap().len(), 39);
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn many_chunks_batched() {
+    // Every chunk is still verified on its own when the chunks are grouped.
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let mut pipeline = continuations_pipeline("many_chunks", Some(&tmp_dir));
+    let dry_run = rust_continuations_dry_run(&mut pipeline, Default::default());
+    let num_chunks = dry_run.bootloader_inputs.len();
+    let max_rows = dry_run.chunk_stats[0].total_rows() as u64 * 2;
+
+    let seen_chunks = std::cell::RefCell::new(vec![]);
+    rust_continuations_batched(
+        &mut pipeline,
+        |first_chunk, pipelines: &mut [Pipeline<GoldilocksField>]| -> Result<(), ()> {
+            for (i, pipeline) in pipelines.iter().enumerate() {
+                run_pilcom_with_backend_variant(pipeline.clone(), BackendVariant::Composite)
+                    .unwrap();
+                seen_chunks.borrow_mut().push(first_chunk + i);
+            }
+            Ok(())
+        },
+        dry_run,
+        max_rows,
+    )
+    .unwrap();
+    assert_eq!(
+        seen_chunks.into_inner(),
+        (0..num_chunks).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn many_chunks_lazy_dry_run() {
+    // The lazy dry run computes the same inputs as the regular one.
+    let mut pipeline = continuations_pipeline("many_chunks", None);
+    let expected = rust_continuations_dry_run(&mut pipeline, Default::default()).bootloader_inputs;
+    assert!(expected.len() > 4);
+
+    let lazy = ContinuationDryRun::new(&mut pipeline, None)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(lazy, expected);
+
+    // Skipping chunks fast-forwards the state without executing them.
+    let mut dry_run = ContinuationDryRun::new(&mut pipeline, None).unwrap();
+    assert_eq!(dry_run.nth(2).unwrap().unwrap(), expected[2]);
+    assert_eq!(dry_run.next().unwrap().unwrap(), expected[3]);
+    let last = expected.len() - 1;
+    assert_eq!(dry_run.nth(last - 4).unwrap().unwrap(), expected[last]);
+    assert!(dry_run.next().is_none());
+}
+
+#[test]
+fn few_chunks_lazy_dry_run_nth() {
+    // Skipping chunks with `nth` fast-forwards over them instead of executing them,
+    // which ends in the same state as executing them.
+    let mut pipeline = few_chunks_pipeline();
+    let expected = ContinuationDryRun::new(&mut pipeline, None)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert!(expected.len() > 2);
+    for k in 0..expected.len() {
+        let mut dry_run = ContinuationDryRun::new(&mut pipeline, None).unwrap();
+        assert_eq!(dry_run.nth(k).unwrap().unwrap(), expected[k], "chunk {k}");
+        assert_eq!(
+            dry_run.next().map(Result::unwrap).as_ref(),
+            expected.get(k + 1)
+        );
+    }
+    let mut dry_run = ContinuationDryRun::new(&mut pipeline, None).unwrap();
+    assert!(dry_run.nth(expected.len()).is_none());
+
+    // Resumed chunks are fast-forwarded as well.
+    let options = DryRunOptions {
+        resume_from: expected[..2]
+            .iter()
+            .map(|(inputs, _)| inputs.clone())
+            .collect(),
+        ..Default::default()
+    };
+    let resumed = rust_continuations_dry_run_with_options(&mut pipeline, None, options).unwrap();
+    assert_eq!(resumed.bootloader_inputs, expected);
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn many_chunks_reverse_order() {
+    // The chunks do not depend on each other, so they can be proven in any order.
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let mut pipeline = continuations_pipeline("many_chunks", Some(&tmp_dir));
+    let dry_run = rust_continuations_dry_run(&mut pipeline, Default::default());
+    assert!(dry_run.bootloader_inputs.len() > 1);
+
+    let mut session = ContinuationSession::new(pipeline);
+    for (i, inputs) in dry_run.bootloader_inputs.into_iter().enumerate().rev() {
+        prove_single_chunk(&mut session, i, inputs, |pipeline| -> Result<(), ()> {
+            run_pilcom_with_backend_variant(pipeline.clone(), BackendVariant::Composite).unwrap();
+            Ok(())
+        })
+        .unwrap();
+    }
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn many_chunks_saved_session() {
+    // A session restored from disk proves the chunks of the original dry run.
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let mut pipeline = continuations_pipeline("many_chunks", Some(&tmp_dir));
+    let dry_run = rust_continuations_dry_run(&mut pipeline, Default::default());
+
+    let session_dir = mktemp::Temp::new_dir().unwrap();
+    ContinuationSession::new(pipeline)
+        .save(&session_dir)
+        .unwrap();
+
+    let output_dir = mktemp::Temp::new_dir().unwrap();
+    let mut session = ContinuationSession::load(&session_dir, || {
+        Pipeline::<GoldilocksField>::default()
+            .with_prover_inputs(Default::default())
+            .with_output(output_dir.to_path_buf(), false)
+    })
+    .unwrap();
+    for (i, inputs) in dry_run.bootloader_inputs.into_iter().enumerate() {
+        prove_single_chunk(&mut session, i, inputs, |pipeline| -> Result<(), ()> {
+            run_pilcom_with_backend_variant(pipeline.clone(), BackendVariant::Composite).unwrap();
+            Ok(())
+        })
+        .unwrap();
+    }
+}
+
+#[test]
+fn global_table_resident_pages() {
+    // Measures the bootloader rows of a program that reads a global table in the
+    // first chunks only, with the pages of the table kept resident.
+    let mut pipeline = continuations_pipeline_with_options(
+        "global_table",
+        None,
+        CompilerOptions::new_gl().with_max_degree_log(14),
+    );
+    let without = rust_continuations_dry_run(&mut pipeline, Default::default());
+    let pages =
+        |stats: &ChunkStats| -> BTreeSet<u32> { stats.accessed_pages.iter().copied().collect() };
+    let chunk_pages = without.chunk_stats.iter().map(pages).collect::<Vec<_>>();
+    assert!(chunk_pages.len() > 2);
+    // The pages of the table are accessed by the first two chunks, but not by the last.
+    let table_pages = &(&chunk_pages[0] & &chunk_pages[1]) - chunk_pages.last().unwrap();
+    assert!(!table_pages.is_empty());
+
+    let options = DryRunOptions {
+        always_resident_pages: table_pages.iter().map(|&page| page as u64).collect(),
+        ..Default::default()
+    };
+    let with = rust_continuations_dry_run_with_options(&mut pipeline, None, options).unwrap();
+    let bootloader_rows =
+        |stats: &[ChunkStats]| -> usize { stats.iter().map(|stats| stats.bootloader_rows).sum() };
+    log::info!(
+        "Bootloader rows for {} table pages: {} without and {} with resident pages",
+        table_pages.len(),
+        bootloader_rows(&without.chunk_stats),
+        bootloader_rows(&with.chunk_stats)
+    );
+    // The first chunk pages in the table either way, so the second chunk starts at
+    // the same row, but keeps the table instead of paging it in.
+    assert_eq!(
+        with.chunk_stats[0].accessed_pages,
+        without.chunk_stats[0].accessed_pages
+    );
+    assert_eq!(
+        with.chunk_stats[0].bootloader_rows,
+        without.chunk_stats[0].bootloader_rows
+    );
+    assert_eq!(pages(&with.chunk_stats[1]), &chunk_pages[1] - &table_pages);
+    assert!(with.chunk_stats[1].bootloader_rows < without.chunk_stats[1].bootloader_rows);
+    assert!(bootloader_rows(&with.chunk_stats) < bootloader_rows(&without.chunk_stats));
+    assert_same_memory_root(&with, &without);
+}
+
+#[test]
+fn continuations_halt_pc() {
+    // The default halt pc is the one the program actually ends at.
+    let mut pipeline = few_chunks_pipeline();
+    rust_continuations_dry_run_with_options(&mut pipeline, None, Default::default()).unwrap();
+
+    // Any other pc is rejected, and the error reports the real halt pc.
+    let options = DryRunOptions {
+        expected_halt_pc: Some(0),
+        ..Default::default()
+    };
+    let Err(ContinuationError::UnexpectedFinalPc { expected, final_pc }) =
+        rust_continuations_dry_run_with_options(&mut pipeline, None, options)
+    else {
+        panic!("expected the final pc check to fail");
+    };
+    assert_eq!(expected, 0);
+    let options = DryRunOptions {
+        expected_halt_pc: Some(final_pc),
+        ..Default::default()
+    };
+    rust_continuations_dry_run_with_options(&mut pipeline, None, options).unwrap();
+}
+
+#[test]
+fn continuations_max_steps() {
+    // A program that halts within exactly `max_steps` steps terminates.
+    let mut pipeline = few_chunks_pipeline();
+    let steps =
+        rust_continuations_dry_run(&mut pipeline, Default::default()).trace_len - FINAL_SINK_ROWS;
+    let options = |max_steps| DryRunOptions {
+        max_steps: Some(max_steps),
+        ..Default::default()
+    };
+    rust_continuations_dry_run_with_options(&mut pipeline, None, options(steps)).unwrap();
+
+    let Err(ContinuationError::NonTerminating { steps: max_steps }) =
+        rust_continuations_dry_run_with_options(&mut pipeline, None, options(steps - 1))
+    else {
+        panic!(
+            "expected the program not to halt within {} steps",
+            steps - 1
+        );
+    };
+    assert_eq!(max_steps, steps - 1);
+}
+
 fn verify_riscv_crate(case: &str, inputs: &[u64], executor_witgen: bool) {
     verify_riscv_crate_gl(
         case,
@@ -0,0 +1,22 @@
+#![no_main]
+#![no_std]
+
+extern crate powdr_riscv_runtime;
+
+use core::hint::black_box;
+
+const N: usize = 1024;
+
+// Keeps updating an array on the stack, so that every chunk writes to the memory
+// and the register memory. Small enough to be split into a few chunks quickly when
+// compiled with a small max degree.
+#[no_mangle]
+pub fn main() {
+    let mut values = [0u32; N];
+    for round in 0..8 {
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = value.wrapping_add(black_box((i + round) as u32));
+        }
+    }
+    black_box(&values);
+}
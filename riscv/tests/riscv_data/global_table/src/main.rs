@@ -0,0 +1,33 @@
+#![no_main]
+#![no_std]
+
+extern crate powdr_riscv_runtime;
+
+use core::hint::black_box;
+
+const N: usize = 1024;
+
+// A lookup table in the data section that is read during the first half of the
+// execution only, so that only the first chunks need to page it in.
+static TABLE: [u32; N] = {
+    let mut table = [0; N];
+    let mut i = 0;
+    while i < N {
+        table[i] = (i as u32).wrapping_mul(2654435761);
+        i += 1;
+    }
+    table
+};
+
+#[no_mangle]
+pub fn main() {
+    let mut acc = 0u32;
+    for i in 0..4000 {
+        let index = (i * 7 + acc as usize) % N;
+        acc = acc.wrapping_add(black_box(&TABLE)[index]);
+    }
+    for i in 0..4000 {
+        acc = black_box(acc.wrapping_mul(3).wrapping_add(i));
+    }
+    black_box(acc);
+}